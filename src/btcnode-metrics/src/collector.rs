@@ -1,551 +1,1249 @@
-use std::time::Instant;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use tracing::{info, warn};
+use prometheus::core::Collector;
+use prometheus::{Gauge, GaugeVec, IntCounter, IntCounterVec};
+use tracing::{error, info, warn};
 
+use crate::config::{CollectorConfig, FeePressureConfig, Section, WatchedDescriptorConfig};
 use crate::metrics::BitcoinMetrics;
-use crate::node::NodeClient;
+use crate::node::{BlockStats, NodeClient, SUPPORTED_CORE_VERSIONS};
+use crate::openmetrics::Exemplar;
+use crate::report::{CollectReport, NodeStatus};
+use crate::snapshot::BitcoinMetricsSnapshot;
+
+/// How long a node status snapshot from the last collection is served before
+/// `status()` refreshes it itself.
+const STATUS_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Number of `size_on_disk` samples the disk-fill estimate is computed over.
+const DISK_SAMPLE_WINDOW: usize = 10;
+
+/// Sections not collected while the node is in initial block download: fee estimates
+/// lack data, and stats of blocks far below the network's tip aren't worth alerting on.
+const IBD_SKIPPED_SECTIONS: [Section; 4] =
+    [Section::ChainTxStats, Section::FeeEstimates, Section::BlockStats, Section::Coinbase];
+
+/// Longest `error` label of `bitcoin_collector_last_error_info`, in characters.
+const ERROR_LABEL_MAX_CHARS: usize = 120;
+
+/// Counts a collection in `bitcoin_collector_scrapes_in_progress` for as long as it's
+/// alive, including when the collection panics.
+struct InProgress<'a>(&'a Gauge);
+
+impl<'a> InProgress<'a> {
+    fn start(gauge: &'a Gauge) -> Self {
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for InProgress<'_> {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Gauge and counter updates gathered during one collection and applied together once it
+/// finishes, so a scrape never observes a half-written collection.
+#[derive(Default)]
+struct PendingUpdates<'a> {
+    resets: Vec<&'a GaugeVec>,
+    gauges: Vec<(&'a Gauge, f64)>,
+    labeled: Vec<(&'a GaugeVec, Vec<String>, f64)>,
+    increments: Vec<&'a IntCounter>,
+    labeled_increments: Vec<(&'a IntCounterVec, Vec<String>)>,
+}
+
+impl<'a> PendingUpdates<'a> {
+    fn set(&mut self, gauge: &'a Gauge, value: f64) {
+        self.gauges.push((gauge, value));
+    }
+
+    /// Stages `value` only if present, leaving the gauge's last value otherwise.
+    fn set_some(&mut self, gauge: &'a Gauge, value: Option<f64>) {
+        if let Some(value) = value {
+            self.set(gauge, value);
+        }
+    }
+
+    /// Clears all series of `gauge_vec` before the staged labeled values are applied.
+    fn reset(&mut self, gauge_vec: &'a GaugeVec) {
+        self.resets.push(gauge_vec);
+    }
+
+    fn set_labeled(&mut self, gauge_vec: &'a GaugeVec, labels: &[&str], value: f64) {
+        self.labeled.push((gauge_vec, labels.iter().map(|l| l.to_string()).collect(), value));
+    }
+
+    fn inc(&mut self, counter: &'a IntCounter) {
+        self.increments.push(counter);
+    }
+
+    fn inc_labeled(&mut self, counter_vec: &'a IntCounterVec, labels: &[&str]) {
+        self.labeled_increments.push((counter_vec, labels.iter().map(|l| l.to_string()).collect()));
+    }
+
+    fn commit(self) {
+        for gauge_vec in self.resets {
+            gauge_vec.reset();
+        }
+        for (gauge, value) in self.gauges {
+            gauge.set(value);
+        }
+        for (gauge_vec, labels, value) in self.labeled {
+            let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+            gauge_vec.with_label_values(&labels).set(value);
+        }
+        for counter in self.increments {
+            counter.inc();
+        }
+        for (counter_vec, labels) in self.labeled_increments {
+            let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+            counter_vec.with_label_values(&labels).inc();
+        }
+    }
+}
+
+/// Nearest-rank percentile of an ascending-sorted, non-empty slice.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Log2 of a big-endian hex integer such as `chainwork`, which overflows `f64` as a raw value.
+///
+/// Returns `None` for an empty, zero or malformed string.
+fn hex_log2(hex: &str) -> Option<f64> {
+    if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let significant = hex.trim_start_matches('0');
+    if significant.is_empty() {
+        return None;
+    }
+    // 13 hex digits (52 bits) fit exactly in an f64 mantissa; the rest only scale it
+    let (leading, rest) = significant.split_at(significant.len().min(13));
+    let mantissa = u64::from_str_radix(leading, 16).ok()? as f64;
+    Some(mantissa.log2() + 4.0 * rest.len() as f64)
+}
+
+/// Seconds until `capacity` is reached at the growth rate between the oldest and newest
+/// sample, `+Inf` when not growing, or `None` until the window spans some time.
+fn disk_full_estimate(samples: &VecDeque<(Instant, f64)>, capacity: f64) -> Option<f64> {
+    let (&(first_at, first), &(last_at, last)) = (samples.front()?, samples.back()?);
+    let elapsed = last_at.duration_since(first_at).as_secs_f64();
+    if elapsed <= 0.0 {
+        return None;
+    }
+    let rate = (last - first) / elapsed;
+    if rate <= 0.0 {
+        return Some(f64::INFINITY);
+    }
+    Some(((capacity - last) / rate).max(0.0))
+}
+
+/// Converts a fee rate from BTC/kvB, as RPCs report it, to sat/vB. Rounds through whole
+/// sat/kvB, the node's own unit, so 0.00001 BTC/kvB comes out as exactly 1.
+fn sat_per_vb(btc_per_kvb: f64) -> f64 {
+    (btc_per_kvb * 100_000_000.0).round() / 1000.0
+}
+
+/// An error message as a bounded label value: whitespace runs collapsed to one space and
+/// anything past `ERROR_LABEL_MAX_CHARS` cut off.
+fn error_label(message: &str) -> String {
+    let collapsed = message.split_whitespace().collect::<Vec<_>>().join(" ");
+    match collapsed.char_indices().nth(ERROR_LABEL_MAX_CHARS) {
+        Some((end, _)) => format!("{}...", &collapsed[..end]),
+        None => collapsed,
+    }
+}
+
+/// `bitcoin_fee_pressure_index`: the weighted mean of the inputs that are known, each scaled
+/// to 0..1. Fee rates (BTC/kvB) scale from `relay_fee` up to `max_fee_rate_sat_vb`; a missing
+/// fee estimate drops out of the mean rather than counting as no pressure.
+fn fee_pressure_index(
+    config: &FeePressureConfig,
+    fullness: Option<f64>,
+    min_fee: f64,
+    relay_fee: f64,
+    estimate: Option<f64>,
+) -> Option<f64> {
+    // 1 sat/vB is 1e-5 BTC/kvB
+    let max_fee = config.max_fee_rate_sat_vb * 1e-5;
+    let scale_fee = |rate: f64| {
+        if max_fee > relay_fee {
+            ((rate - relay_fee) / (max_fee - relay_fee)).clamp(0.0, 1.0)
+        } else if rate > relay_fee {
+            1.0
+        } else {
+            0.0
+        }
+    };
+    let inputs = [
+        fullness.map(|fullness| (config.mempool_weight, fullness.clamp(0.0, 1.0))),
+        Some((config.min_fee_weight, scale_fee(min_fee))),
+        estimate.map(|rate| (config.estimate_weight, scale_fee(rate))),
+    ];
+    let (weighted, weights) = inputs
+        .into_iter()
+        .flatten()
+        .fold((0.0, 0.0), |(sum, total), (weight, value)| (sum + weight * value, total + weight));
+    (weights > 0.0).then(|| weighted / weights)
+}
+
+/// Seconds from a block's header `time` to `now` (both UNIX epoch seconds).
+///
+/// Miners may date a block up to two hours ahead and the local clock can be skewed, so
+/// a best block from "the future" is reported as no lag rather than a negative one.
+fn block_timestamp_lag(now: i64, block_time: i64) -> f64 {
+    now.saturating_sub(block_time).max(0) as f64
+}
+
+/// Per-second rate of a byte total between two samples taken `elapsed_millis` apart;
+/// `None` when no time passed or the total went backwards (node restart).
+fn bytes_per_second(previous: u64, current: u64, elapsed_millis: u64) -> Option<f64> {
+    if elapsed_millis == 0 || current < previous {
+        return None;
+    }
+    Some((current - previous) as f64 * 1000.0 / elapsed_millis as f64)
+}
+
+/// Coinbase scriptSig tags of well-known pools, checked in order; the first match names the pool.
+const POOL_TAGS: &[(&str, &str)] = &[
+    ("Foundry USA", "Foundry USA"),
+    ("AntPool", "AntPool"),
+    ("ViaBTC", "ViaBTC"),
+    ("F2Pool", "F2Pool"),
+    ("Binance", "Binance Pool"),
+    ("MARA Pool", "MARA Pool"),
+    ("SpiderPool", "SpiderPool"),
+    ("SECPOOL", "SECPOOL"),
+    ("Luxor", "Luxor"),
+    ("LUXOR", "Luxor"),
+    ("slush", "Braiins Pool"),
+    ("poolin", "Poolin"),
+    ("OCEAN", "OCEAN"),
+    ("BTC.com", "BTC.com"),
+];
+
+/// Pool named by a tag in a coinbase's hex scriptSig, if any known tag appears in it.
+fn pool_tag(coinbase_hex: &str) -> Option<&'static str> {
+    let script: Vec<u8> = (0..coinbase_hex.len() / 2)
+        .filter_map(|i| u8::from_str_radix(coinbase_hex.get(2 * i..2 * i + 2)?, 16).ok())
+        .collect();
+    POOL_TAGS
+        .iter()
+        .find(|(tag, _)| script.windows(tag.len()).any(|window| window == tag.as_bytes()))
+        .map(|&(_, pool)| pool)
+}
+
+/// Non-empty warning texts; a lone empty string is how Core reports "no warnings"
+/// when a list carries over the older single-string form.
+fn non_empty_warnings(warnings: &[String]) -> impl Iterator<Item = String> + '_ {
+    warnings.iter().map(|w| w.trim()).filter(|w| !w.is_empty()).map(str::to_string)
+}
 
 pub struct MetricsCollector<N: NodeClient> {
     node: N,
     metrics: BitcoinMetrics,
+    /// Swapped whole on a config reload; each collection uses the one current at its start.
+    config: RwLock<Arc<CollectorConfig>>,
+    status: Mutex<Option<(Instant, NodeStatus)>>,
+    disk_samples: Mutex<VecDeque<(Instant, f64)>>,
+    /// `(time_millis, total_bytes_received, total_bytes_sent)` from the previous `getnettotals`.
+    last_net_totals: Mutex<Option<(u64, u64, u64)>>,
+    /// `(hash, time, interval)` of the tip at the previous collection, so an unchanged tip
+    /// needs no `getblockheader` and the next one only its own header.
+    last_tip: Mutex<Option<(String, i64, Option<f64>)>>,
+    /// `(height, hash)` of the latest tip, which the next one must build on to not be a reorg.
+    reorg_tip: Mutex<Option<(i64, String)>>,
+    /// `(tip hash, requested fields, stats)` of the last successful `getblockstats`, reused
+    /// until the tip or `block_stats_fields` changes since a block's stats never do.
+    last_block_stats: Mutex<Option<(String, Vec<String>, BlockStats)>>,
+    /// Error label of each RPC method's latest failure, kept until the method succeeds again.
+    last_errors: Mutex<BTreeMap<String, String>>,
+    last_collect_start: Mutex<Option<Instant>>,
+    /// Latest exemplar of each scrape duration histogram bucket, by bucket index.
+    scrape_exemplars: Mutex<BTreeMap<usize, Exemplar>>,
+    /// When the node was first seen out of initial block download; cleared when it re-enters
+    /// it, e.g. for a reindex.
+    synced_since: Mutex<Option<Instant>>,
+    expected_chain: Option<String>,
+    max_connections: Option<u32>,
+    wallets: Vec<String>,
+    watched_descriptors: Vec<WatchedDescriptorConfig>,
+    /// Whether the last `getblockchaininfo` reported a chain other than `expected_chain`.
+    chain_mismatch: AtomicBool,
 }
 
 impl<N: NodeClient> MetricsCollector<N> {
     pub fn new(node: N, metrics: BitcoinMetrics) -> Self {
-        Self { node, metrics }
+        Self::with_config(node, metrics, CollectorConfig::default())
+    }
+
+    pub fn with_config(node: N, metrics: BitcoinMetrics, config: CollectorConfig) -> Self {
+        Self {
+            node,
+            metrics,
+            config: RwLock::new(Arc::new(config)),
+            status: Mutex::new(None),
+            disk_samples: Mutex::new(VecDeque::with_capacity(DISK_SAMPLE_WINDOW)),
+            last_net_totals: Mutex::new(None),
+            last_tip: Mutex::new(None),
+            reorg_tip: Mutex::new(None),
+            last_block_stats: Mutex::new(None),
+            last_errors: Mutex::new(BTreeMap::new()),
+            last_collect_start: Mutex::new(None),
+            scrape_exemplars: Mutex::new(BTreeMap::new()),
+            synced_since: Mutex::new(None),
+            expected_chain: None,
+            max_connections: None,
+            wallets: Vec::new(),
+            watched_descriptors: Vec::new(),
+            chain_mismatch: AtomicBool::new(false),
+        }
+    }
+
+    /// Flags (`bitcoin_chain_mismatch`) a node that reports a chain other than `chain`.
+    pub fn with_expected_chain(mut self, chain: Option<String>) -> Self {
+        self.expected_chain = chain;
+        self
+    }
+
+    /// Exports connections relative to the node's configured `-maxconnections`.
+    pub fn with_max_connections(mut self, max_connections: Option<u32>) -> Self {
+        self.max_connections = max_connections.filter(|&max| max > 0);
+        self
+    }
+
+    /// Exports `getwalletinfo` balances of these wallets, labeled by wallet.
+    pub fn with_wallets(mut self, wallets: Vec<String>) -> Self {
+        self.wallets = wallets;
+        self
+    }
+
+    /// Exports the balance of these descriptors on [`MetricsCollector::scan_descriptors`],
+    /// labeled by name.
+    pub fn with_watched_descriptors(mut self, descriptors: Vec<WatchedDescriptorConfig>) -> Self {
+        self.watched_descriptors = descriptors;
+        self
+    }
+
+    pub fn watched_descriptors(&self) -> &[WatchedDescriptorConfig] {
+        &self.watched_descriptors
+    }
+
+    /// Exemplars of the scrape duration histogram, for [`crate::openmetrics::encode`].
+    pub fn scrape_duration_exemplars(&self) -> Vec<Exemplar> {
+        self.scrape_exemplars.lock().expect("exemplars lock poisoned").values().cloned().collect()
+    }
+
+    fn record_exemplar(&self, span_id: u64, duration: f64) {
+        let families = self.metrics.scrape_duration_histogram.collect();
+        let Some(metric) = families.first().and_then(|family| family.get_metric().first()) else { return };
+        let buckets = metric.get_histogram().get_bucket();
+        // Past the last bucket is the implicit +Inf one
+        let bucket = buckets.iter().position(|bucket| duration <= bucket.upper_bound()).unwrap_or(buckets.len());
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or_default();
+        let exemplar = Exemplar { span_id, value: duration, timestamp };
+        self.scrape_exemplars.lock().expect("exemplars lock poisoned").insert(bucket, exemplar);
+    }
+
+    /// Looks up the balance of each watched descriptor with `scantxoutset`. Each scan reads the
+    /// whole UTXO set, so this runs on `descriptor_scan_interval_seconds` rather than as part of
+    /// [`MetricsCollector::collect`]; a failed scan keeps the descriptor's previous balance.
+    pub fn scan_descriptors(&self) -> CollectReport {
+        let mut report = CollectReport::default();
+        for watched in &self.watched_descriptors {
+            let labels = [watched.name.as_str()];
+            let method = format!("scantxoutset/{}", watched.name);
+            match report.track(&method, || self.node.scan_tx_out_set(&watched.descriptor)) {
+                Ok(scan) if scan.success => {
+                    self.metrics.watched_descriptor_balance.with_label_values(&labels).set(scan.total_amount);
+                    info!("Updated watched descriptor {}: balance={}", watched.name, scan.total_amount);
+                }
+                Ok(_) => {
+                    warn!("scantxoutset for watched descriptor {} was aborted", watched.name);
+                    self.metrics.watched_descriptor_scan_errors.with_label_values(&labels).inc();
+                }
+                Err(e) => {
+                    warn!("Failed to scan watched descriptor {}: {e}", watched.name);
+                    self.metrics.watched_descriptor_scan_errors.with_label_values(&labels).inc();
+                }
+            }
+        }
+        report
     }
 
     pub fn metrics(&self) -> &BitcoinMetrics {
         &self.metrics
     }
 
-    pub fn collect(&self) {
+    /// Current metric values as a plain struct, e.g. right after [`MetricsCollector::collect`].
+    pub fn snapshot(&self) -> BitcoinMetricsSnapshot {
+        BitcoinMetricsSnapshot::of(&self.metrics)
+    }
+
+    pub fn config(&self) -> Arc<CollectorConfig> {
+        self.config.read().expect("collector config lock poisoned").clone()
+    }
+
+    /// Replaces the collector config from the next collection on. The caller keeps the
+    /// registered sections in step (see [`BitcoinMetrics::set_sections`]).
+    pub fn set_config(&self, config: CollectorConfig) {
+        *self.config.write().expect("collector config lock poisoned") = Arc::new(config);
+    }
+
+    /// Node status from the most recent collection, refreshed with `getblockchaininfo`
+    /// and `uptime` only when no collection has run recently.
+    pub fn status(&self) -> NodeStatus {
+        if let Some((at, status)) = &*self.status.lock().expect("status lock poisoned")
+            && at.elapsed() < STATUS_MAX_AGE
+        {
+            return status.clone();
+        }
+
+        let blockchain = self
+            .node
+            .get_blockchain_info()
+            .ok()
+            .map(|info| (info.blocks, info.verification_progress, info.initial_block_download));
+        let status = NodeStatus::new(blockchain, self.node.uptime().ok());
+        *self.status.lock().expect("status lock poisoned") = Some((Instant::now(), status.clone()));
+        status
+    }
+
+    /// Seconds between the header timestamps of the tip and its parent, fetching only the
+    /// headers not already known from the previous collection.
+    fn last_block_interval(&self, report: &mut CollectReport, hash: &str, time: i64) -> Option<f64> {
+        let mut last_tip = self.last_tip.lock().expect("last tip lock poisoned");
+        if let Some((last_hash, _, interval)) = &*last_tip
+            && last_hash == hash
+        {
+            return *interval;
+        }
+        let parent = match report.track("getblockheader", || self.node.get_block_header(hash)) {
+            Ok(header) => header.previous_block_hash?,
+            Err(e) => {
+                warn!("Failed to get block header {hash}: {e}");
+                return None;
+            }
+        };
+        let parent_time = match &*last_tip {
+            Some((last_hash, last_time, _)) if *last_hash == parent => *last_time,
+            _ => match report.track("getblockheader", || self.node.get_block_header(&parent)) {
+                Ok(header) => header.time,
+                Err(e) => {
+                    warn!("Failed to get block header {parent}: {e}");
+                    return None;
+                }
+            },
+        };
+        let interval = Some((time - parent_time) as f64);
+        *last_tip = Some((hash.to_string(), time, interval));
+        interval
+    }
+
+    /// Whether the tip at `height` replaced blocks of the previous one: it's no higher but
+    /// different, or the previous tip is no longer its ancestor. When `getblockhash` fails the
+    /// previous tip is kept, to check against on the next collection.
+    fn reorged(&self, report: &mut CollectReport, height: i64, hash: &str) -> bool {
+        let mut reorg_tip = self.reorg_tip.lock().expect("reorg tip lock poisoned");
+        let reorged = match &*reorg_tip {
+            None => false,
+            Some((_, previous_hash)) if previous_hash == hash => return false,
+            Some((previous_height, _)) if height <= *previous_height => true,
+            Some((previous_height, previous_hash)) => {
+                match report.track("getblockhash", || self.node.get_block_hash(*previous_height)) {
+                    Ok(ancestor) => ancestor != *previous_hash,
+                    Err(e) => {
+                        warn!("Failed to get block hash at height {previous_height}: {e}");
+                        return false;
+                    }
+                }
+            }
+        };
+        if reorged && let Some((previous_height, previous_hash)) = &*reorg_tip {
+            warn!("Reorg: tip {hash} at height {height} replaced {previous_hash} at height {previous_height}");
+        }
+        *reorg_tip = Some((height, hash.to_string()));
+        reorged
+    }
+
+    pub fn collect(&self) -> CollectReport {
+        let _in_progress = InProgress::start(&self.metrics.scrapes_in_progress);
+        let config = self.config();
         let start = Instant::now();
-        let mut had_error = false;
+        let previous_start = self.last_collect_start.lock().expect("collect start lock poisoned").replace(start);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+        let mut report = CollectReport::default();
         let mut block_height: Option<i64> = None;
+        let mut best_block_hash: Option<String> = None;
+        let mut pending = PendingUpdates::default();
+        if let Some(previous_start) = previous_start {
+            pending.set(&self.metrics.seconds_since_last_scrape, start.duration_since(previous_start).as_secs_f64());
+        }
+        // Warnings are merged from blockchain and network info; `None` until either succeeds
+        let mut warnings: Option<BTreeSet<String>> = None;
+        let mut blockchain_status: Option<(i64, f64, bool)> = None;
+        let mut uptime_seconds: Option<u32> = None;
+        // Inputs of the fee pressure index: (fullness, min fee, relay fee) and the 2-block estimate
+        let mut mempool_fees: Option<(Option<f64>, f64, f64)> = None;
+        let mut short_fee_estimate: Option<f64> = None;
+        let mut limited_networks: Option<BTreeSet<String>> = None;
+        // Sections whose values were all refreshed by this collection
+        let mut fresh: BTreeSet<Section> = BTreeSet::new();
 
         // Blockchain info
-        match self.node.get_blockchain_info() {
-            Ok(info) => {
-                self.metrics.blocks.set(info.blocks as f64);
-                self.metrics.headers.set(info.headers as f64);
-                self.metrics.difficulty.set(info.difficulty);
-                self.metrics.verification_progress.set(info.verification_progress);
-                self.metrics.size_on_disk.set(info.size_on_disk as f64);
-                self.metrics.initial_block_download.set(if info.initial_block_download { 1.0 } else { 0.0 });
-                self.metrics.chain_pruned.set(if info.pruned { 1.0 } else { 0.0 });
-                block_height = Some(info.blocks);
-                info!("Updated blockchain info: blocks={}, headers={}", info.blocks, info.headers);
-            }
-            Err(e) => {
-                warn!("Failed to get blockchain info: {e}");
-                had_error = true;
+        if config.is_enabled(Section::Blockchain) {
+            match report.track("getblockchaininfo", || self.node.get_blockchain_info()) {
+                Ok(info) => {
+                    fresh.insert(Section::Blockchain);
+                    pending.set(&self.metrics.blocks, info.blocks as f64);
+                    pending.set(&self.metrics.headers, info.headers as f64);
+                    pending.set(&self.metrics.headers_blocks_gap, (info.headers - info.blocks).max(0) as f64);
+                    pending.set(&self.metrics.difficulty, info.difficulty);
+                    // Regtest's minimum difficulty is below 1, so this goes negative there
+                    if info.difficulty > 0.0 {
+                        pending.set(&self.metrics.difficulty_log2, info.difficulty.log2());
+                    }
+                    pending.set(&self.metrics.verification_progress, info.verification_progress);
+                    pending.set(&self.metrics.size_on_disk, info.size_on_disk as f64);
+                    if let Some(capacity) = config.disk_capacity_bytes {
+                        let size = info.size_on_disk as f64;
+                        let mut samples = self.disk_samples.lock().expect("disk samples lock poisoned");
+                        // A shrinking size (pruning, reindex) would skew the rate, so start over
+                        if samples.back().is_some_and(|&(_, last)| size < last) {
+                            samples.clear();
+                        }
+                        if samples.len() == DISK_SAMPLE_WINDOW {
+                            samples.pop_front();
+                        }
+                        samples.push_back((Instant::now(), size));
+                        if let Some(estimate) = disk_full_estimate(&samples, capacity as f64) {
+                            pending.set_labeled(&self.metrics.disk_full_estimate_seconds, &[], estimate);
+                        }
+                    }
+                    pending.set(&self.metrics.initial_block_download, if info.initial_block_download { 1.0 } else { 0.0 });
+                    pending.set(&self.metrics.node_in_ibd, if info.initial_block_download { 1.0 } else { 0.0 });
+                    let mut synced_since = self.synced_since.lock().expect("synced since lock poisoned");
+                    let seconds_since_synced = if info.initial_block_download {
+                        *synced_since = None;
+                        0.0
+                    } else {
+                        start.duration_since(*synced_since.get_or_insert(start)).as_secs_f64()
+                    };
+                    drop(synced_since);
+                    pending.set(&self.metrics.seconds_since_synced, seconds_since_synced);
+                    let synced = !info.initial_block_download && info.verification_progress >= config.synced_threshold;
+                    pending.set(&self.metrics.node_synced, if synced { 1.0 } else { 0.0 });
+                    pending.set(&self.metrics.chain_pruned, if info.pruned { 1.0 } else { 0.0 });
+                    if let Some(work) = hex_log2(&info.chain_work) {
+                        pending.set(&self.metrics.chain_work_log2, work);
+                    }
+                    if let Some(expected) = &self.expected_chain {
+                        let mismatch = info.chain != *expected;
+                        // Log only when the mismatch starts, not on every scrape
+                        if mismatch && !self.chain_mismatch.swap(true, Ordering::Relaxed) {
+                            error!("Node is on chain {:?} but expected_chain is {expected:?}; its metrics describe the wrong chain", info.chain);
+                        } else if !mismatch {
+                            self.chain_mismatch.store(false, Ordering::Relaxed);
+                        }
+                        pending.set_labeled(&self.metrics.chain_mismatch, &[], if mismatch { 1.0 } else { 0.0 });
+                    }
+                    pending.set(&self.metrics.best_block_timestamp, info.time as f64);
+                    if self.reorged(&mut report, info.blocks, &info.best_block_hash) {
+                        pending.inc(&self.metrics.reorgs);
+                    }
+                    // Only the current tip's series, so the hash label can't pile up series
+                    pending.reset(&self.metrics.best_block_info);
+                    pending.set_labeled(&self.metrics.best_block_info, &[&info.best_block_hash], 1.0);
+                    pending.set(&self.metrics.block_timestamp_lag_seconds, block_timestamp_lag(now, info.time));
+                    // Genesis has no parent to measure from
+                    if info.blocks > 0
+                        && let Some(interval) = self.last_block_interval(&mut report, &info.best_block_hash, info.time)
+                    {
+                        pending.set_labeled(&self.metrics.last_block_interval_seconds, &[], interval);
+                    }
+                    block_height = Some(info.blocks);
+                    best_block_hash = Some(info.best_block_hash.clone());
+                    blockchain_status = Some((info.blocks, info.verification_progress, info.initial_block_download));
+                    warnings.get_or_insert_default().extend(non_empty_warnings(&info.warnings));
+                    info!("Updated blockchain info: blocks={}, headers={}", info.blocks, info.headers);
+                }
+                Err(e) => {
+                    warn!("Failed to get blockchain info: {e}");
+                }
             }
         }
 
+        // During IBD, skip sections that make no sense yet instead of failing them; without
+        // blockchain info the node isn't known to be in IBD, so everything is collected
+        let in_ibd = blockchain_status.is_some_and(|(_, _, ibd)| ibd);
+        if in_ibd {
+            info!("Node is in initial block download; skipping {IBD_SKIPPED_SECTIONS:?}");
+        }
+        let collects =
+            |section: Section| config.is_enabled(section) && !(in_ibd && IBD_SKIPPED_SECTIONS.contains(&section));
+
         // Mempool info
-        match self.node.get_mempool_info() {
-            Ok(info) => {
-                self.metrics.mempool_transactions.set(info.size as f64);
-                self.metrics.mempool_bytes.set(info.bytes as f64);
-                self.metrics.mempool_usage.set(info.usage as f64);
-                self.metrics.mempool_max_bytes.set(info.max_mempool as f64);
-                self.metrics.mempool_min_fee.set(info.mempool_min_fee);
-                self.metrics.mempool_total_fee.set(info.total_fee);
-                self.metrics.mempool_min_relay_tx_fee.set(info.min_relay_tx_fee);
-                self.metrics.mempool_incremental_relay_fee.set(info.incremental_relay_fee);
-                self.metrics.mempool_unbroadcast_count.set(info.unbroadcast_count as f64);
-                self.metrics.mempool_full_rbf.set(if info.full_rbf { 1.0 } else { 0.0 });
-                info!("Updated mempool info: txs={}, bytes={}", info.size, info.bytes);
+        if config.is_enabled(Section::Mempool) {
+            match report.track("getmempoolinfo", || self.node.get_mempool_info()) {
+                Ok(info) => {
+                    fresh.insert(Section::Mempool);
+                    pending.set(&self.metrics.mempool_transactions, info.size as f64);
+                    pending.set(&self.metrics.mempool_bytes, info.bytes as f64);
+                    pending.set(&self.metrics.mempool_usage, info.usage as f64);
+                    pending.set(&self.metrics.mempool_max_bytes, info.max_mempool as f64);
+                    pending.set(&self.metrics.mempool_min_fee, info.mempool_min_fee);
+                    pending.set(&self.metrics.mempool_total_fee, info.total_fee);
+                    // Rounded, as BTC amounts are whole satoshis the float can't represent exactly
+                    pending.set(&self.metrics.mempool_total_fee_sat, (info.total_fee * 100_000_000.0).round());
+                    pending.set(&self.metrics.mempool_min_relay_tx_fee, info.min_relay_tx_fee);
+                    // The minimum fee only rises above the relay fee once a full mempool evicts
+                    let above_relay = info.mempool_min_fee > info.min_relay_tx_fee;
+                    pending.set(&self.metrics.mempool_min_fee_above_relay, if above_relay { 1.0 } else { 0.0 });
+                    if info.min_relay_tx_fee > 0.0 {
+                        pending.set(&self.metrics.mempool_min_fee_ratio, info.mempool_min_fee / info.min_relay_tx_fee);
+                    }
+                    let headroom = info.max_mempool.saturating_sub(info.usage);
+                    pending.set(&self.metrics.mempool_headroom_bytes, headroom as f64);
+                    let threshold = info.max_mempool as f64 * config.mempool_eviction_headroom_percent / 100.0;
+                    let imminent = info.max_mempool > 0 && (headroom as f64) < threshold;
+                    pending.set(&self.metrics.mempool_eviction_imminent, if imminent { 1.0 } else { 0.0 });
+                    pending.set(&self.metrics.mempool_incremental_relay_fee, info.incremental_relay_fee);
+                    pending.set(&self.metrics.mempool_unbroadcast_count, info.unbroadcast_count as f64);
+                    pending.set(&self.metrics.mempool_full_rbf, if info.full_rbf { 1.0 } else { 0.0 });
+                    let fullness = (info.max_mempool > 0).then(|| info.usage as f64 / info.max_mempool as f64);
+                    mempool_fees = Some((fullness, info.mempool_min_fee, info.min_relay_tx_fee));
+                    info!("Updated mempool info: txs={}, bytes={}", info.size, info.bytes);
+                }
+                Err(e) => {
+                    warn!("Failed to get mempool info: {e}");
+                }
             }
-            Err(e) => {
-                warn!("Failed to get mempool info: {e}");
-                had_error = true;
+        }
+
+        // Mempool detail (opt-in; one verbose fetch feeds every per-transaction statistic)
+        if config.is_enabled(Section::MempoolDetail) {
+            match report.track("getrawmempool", || self.node.get_raw_mempool_verbose()) {
+                Ok(mempool) => {
+                    fresh.insert(Section::MempoolDetail);
+                    let mut vsizes: Vec<f64> = mempool.0.values().map(|entry| entry.vsize as f64).collect();
+                    vsizes.sort_by(f64::total_cmp);
+                    if vsizes.is_empty() {
+                        pending.set(&self.metrics.mempool_tx_vsize_p50, 0.0);
+                        pending.set(&self.metrics.mempool_tx_vsize_p90, 0.0);
+                        pending.set(&self.metrics.mempool_tx_vsize_p99, 0.0);
+                    } else {
+                        pending.set(&self.metrics.mempool_tx_vsize_p50, percentile(&vsizes, 0.50));
+                        pending.set(&self.metrics.mempool_tx_vsize_p90, percentile(&vsizes, 0.90));
+                        pending.set(&self.metrics.mempool_tx_vsize_p99, percentile(&vsizes, 0.99));
+                    }
+                    // A transaction stuck unbroadcast means the node can't relay its own, e.g.
+                    // without outbound peers
+                    let oldest_unbroadcast =
+                        mempool.0.values().filter(|entry| entry.unbroadcast).map(|entry| entry.time).min();
+                    let age = oldest_unbroadcast.map_or(0, |time| (now - time).max(0));
+                    pending.set(&self.metrics.mempool_oldest_unbroadcast_seconds, age as f64);
+                    info!("Updated mempool detail: txs={}", vsizes.len());
+                }
+                Err(e) => {
+                    warn!("Failed to get verbose mempool: {e}");
+                }
             }
         }
 
         // Network info
-        match self.node.get_network_info() {
-            Ok(info) => {
-                self.metrics.connections.set(info.connections as f64);
-                self.metrics.connections_in.set(info.connections_in as f64);
-                self.metrics.connections_out.set(info.connections_out as f64);
-                self.metrics.network_active.set(if info.network_active { 1.0 } else { 0.0 });
-                self.metrics.node_version.set(info.version as f64);
-                self.metrics.protocol_version.set(info.protocol_version as f64);
-                self.metrics.time_offset.set(info.time_offset as f64);
-                self.metrics.relay_fee.set(info.relay_fee);
-                self.metrics.incremental_fee.set(info.incremental_fee);
-                info!("Updated network info: connections={}", info.connections);
-            }
-            Err(e) => {
-                warn!("Failed to get network info: {e}");
-                had_error = true;
+        if config.is_enabled(Section::Network) {
+            match report.track("getnetworkinfo", || self.node.get_network_info()) {
+                Ok(info) => {
+                    fresh.insert(Section::Network);
+                    pending.set(&self.metrics.connections, info.connections as f64);
+                    pending.set(&self.metrics.connections_in, info.connections_in as f64);
+                    pending.set(&self.metrics.connections_out, info.connections_out as f64);
+                    if let Some(max) = self.max_connections {
+                        let ratio = info.connections as f64 / f64::from(max);
+                        pending.set_labeled(&self.metrics.connections_saturation_ratio, &[], ratio);
+                    }
+                    pending.set(&self.metrics.network_active, if info.network_active { 1.0 } else { 0.0 });
+                    // No advertised address usually means the node isn't reachable, e.g. behind NAT
+                    let accepting_inbound = info.network_active && !info.local_addresses.is_empty();
+                    pending.set(&self.metrics.accepting_inbound, if accepting_inbound { 1.0 } else { 0.0 });
+                    pending.set(&self.metrics.local_addresses_count, info.local_addresses.len() as f64);
+                    pending.set(&self.metrics.node_version, info.version as f64);
+                    pending.set(&self.metrics.protocol_version, info.protocol_version as f64);
+                    let supported = SUPPORTED_CORE_VERSIONS.contains(&(info.version as u64));
+                    pending.set(&self.metrics.core_version_supported, if supported { 1.0 } else { 0.0 });
+                    pending.set(&self.metrics.time_offset, info.time_offset as f64);
+                    pending.set(&self.metrics.relay_fee, info.relay_fee);
+                    pending.set(&self.metrics.incremental_fee, info.incremental_fee);
+                    pending.set(&self.metrics.relay_fee_sat_per_vb, sat_per_vb(info.relay_fee));
+                    pending.set(&self.metrics.incremental_fee_sat_per_vb, sat_per_vb(info.incremental_fee));
+                    warnings.get_or_insert_default().extend(non_empty_warnings(&info.warnings));
+                    pending.reset(&self.metrics.network_reachable);
+                    pending.reset(&self.metrics.network_proxy_configured);
+                    pending.reset(&self.metrics.network_limited);
+                    for network in &info.networks {
+                        let limited = if network.limited { 1.0 } else { 0.0 };
+                        pending.set_labeled(&self.metrics.network_limited, &[&network.name], limited);
+                        pending.set_labeled(&self.metrics.network_reachable, &[&network.name], if network.reachable { 1.0 } else { 0.0 });
+                        let proxied = if network.proxy.is_empty() { 0.0 } else { 1.0 };
+                        pending.set_labeled(&self.metrics.network_proxy_configured, &[&network.name], proxied);
+                    }
+                    let limited = info.networks.iter().filter(|network| network.limited);
+                    limited_networks = Some(limited.map(|network| network.name.clone()).collect());
+                    info!("Updated network info: connections={}", info.connections);
+                }
+                Err(e) => {
+                    warn!("Failed to get network info: {e}");
+                }
             }
         }
 
         // Peer info (aggregated)
-        match self.node.get_peer_info() {
-            Ok(peers) => {
-                let total = peers.0.len();
-                let inbound = peers.0.iter().filter(|p| p.inbound).count();
-                let outbound = total - inbound;
-                let total_sent: u64 = peers.0.iter().map(|p| p.bytes_sent).sum();
-                let total_recv: u64 = peers.0.iter().map(|p| p.bytes_received).sum();
-                let ping_sum: f64 = peers.0.iter().filter_map(|p| p.ping_time).sum();
-                let ping_count = peers.0.iter().filter(|p| p.ping_time.is_some()).count();
-                let avg_ping = if ping_count > 0 { ping_sum / ping_count as f64 } else { 0.0 };
-
-                self.metrics.peer_count.set(total as f64);
-                self.metrics.peers_inbound.set(inbound as f64);
-                self.metrics.peers_outbound.set(outbound as f64);
-                self.metrics.peers_total_bytes_sent.set(total_sent as f64);
-                self.metrics.peers_total_bytes_received.set(total_recv as f64);
-                self.metrics.peers_avg_ping_seconds.set(avg_ping);
-                info!("Updated peer info: peers={} (in={}, out={})", total, inbound, outbound);
-            }
-            Err(e) => {
-                warn!("Failed to get peer info: {e}");
-                had_error = true;
+        if config.is_enabled(Section::Peers) {
+            match report.track("getpeerinfo", || self.node.get_peer_info()) {
+                Ok(peers) => {
+                    fresh.insert(Section::Peers);
+                    let total = peers.0.len();
+                    let inbound = peers.0.iter().filter(|p| p.inbound).count();
+                    let outbound = total - inbound;
+                    let block_relay = peers
+                        .0
+                        .iter()
+                        .filter(|p| !p.inbound && p.connection_type.as_deref() == Some("block-relay-only"))
+                        .count();
+                    let total_sent: u64 = peers.0.iter().map(|p| p.bytes_sent).sum();
+                    let total_recv: u64 = peers.0.iter().map(|p| p.bytes_received).sum();
+                    let ping_sum: f64 = peers.0.iter().filter_map(|p| p.ping_time).sum();
+                    let ping_count = peers.0.iter().filter(|p| p.ping_time.is_some()).count();
+                    let avg_ping = if ping_count > 0 { ping_sum / ping_count as f64 } else { 0.0 };
+                    // Best-case latency, steadier than ping_time; peers without one yet are skipped
+                    let min_pings: Vec<f64> = peers.0.iter().filter_map(|p| p.minimum_ping).collect();
+                    let avg_min_ping =
+                        (!min_pings.is_empty()).then(|| min_pings.iter().sum::<f64>() / min_pings.len() as f64);
+                    let worst_min_ping = min_pings.iter().copied().reduce(f64::max);
+                    // A connection time that's unset or in the future can't give a meaningful age
+                    let ages: Vec<i64> = peers
+                        .0
+                        .iter()
+                        .map(|p| p.connection_time)
+                        .filter(|time| (1..=now).contains(time))
+                        .map(|time| now - time)
+                        .collect();
+                    let avg_age = (!ages.is_empty()).then(|| ages.iter().sum::<i64>() as f64 / ages.len() as f64);
+                    let min_age = ages.iter().min().map(|&age| age as f64);
+                    let min_fee_filter = peers.0.iter().map(|p| p.minimum_fee_filter).reduce(f64::min);
+                    let max_fee_filter = peers.0.iter().map(|p| p.minimum_fee_filter).reduce(f64::max);
+                    let mut by_transport: BTreeMap<&str, usize> = BTreeMap::new();
+                    let mut by_network: BTreeMap<&str, usize> = BTreeMap::new();
+                    for peer in &peers.0 {
+                        *by_transport.entry(peer.transport_protocol_type.as_str()).or_default() += 1;
+                        *by_network.entry(peer.network.as_str()).or_default() += 1;
+                    }
+                    // Peers that never relayed a block report 0
+                    let last_block = peers.0.iter().map(|p| p.last_block).filter(|&time| time > 0).max();
+                    let addresses_processed: usize = peers.0.iter().filter_map(|p| p.addresses_processed).sum();
+                    let addresses_rate_limited: usize = peers.0.iter().filter_map(|p| p.addresses_rate_limited).sum();
+                    // Peers report -1 until their header height is known
+                    let max_peer_headers = peers
+                        .0
+                        .iter()
+                        .flat_map(|p| [p.synced_headers, p.starting_height])
+                        .flatten()
+                        .filter(|&height| height >= 0)
+                        .max();
+
+                    pending.set(&self.metrics.peer_count, total as f64);
+                    pending.set(&self.metrics.peers_inbound, inbound as f64);
+                    pending.set(&self.metrics.peers_outbound, outbound as f64);
+                    pending.set(&self.metrics.outbound_block_relay_connections, block_relay as f64);
+                    pending.set(&self.metrics.peers_total_bytes_sent, total_sent as f64);
+                    pending.set(&self.metrics.peers_total_bytes_received, total_recv as f64);
+                    pending.set(&self.metrics.peers_avg_ping_seconds, avg_ping);
+                    pending.set_some(&self.metrics.peers_avg_min_ping_seconds, avg_min_ping);
+                    pending.set_some(&self.metrics.peers_worst_min_ping_seconds, worst_min_ping);
+                    pending.set_some(&self.metrics.peers_avg_connection_age_seconds, avg_age);
+                    pending.set_some(&self.metrics.peers_min_connection_age_seconds, min_age);
+                    pending.set(&self.metrics.peers_addresses_processed, addresses_processed as f64);
+                    // Reset so a transport no peer uses anymore drops out
+                    pending.reset(&self.metrics.peers_by_transport);
+                    for (transport, count) in &by_transport {
+                        pending.set_labeled(&self.metrics.peers_by_transport, &[transport], *count as f64);
+                    }
+                    pending.reset(&self.metrics.peers_by_network);
+                    for (network, count) in &by_network {
+                        pending.set_labeled(&self.metrics.peers_by_network, &[network], *count as f64);
+                    }
+                    pending.reset(&self.metrics.peers_on_limited_networks);
+                    if let Some(limited) = &limited_networks {
+                        let leaked = peers.0.iter().filter(|p| limited.contains(&p.network)).count();
+                        pending.set_labeled(&self.metrics.peers_on_limited_networks, &[], leaked as f64);
+                    }
+                    pending.set(&self.metrics.peers_addresses_rate_limited, addresses_rate_limited as f64);
+                    if let (Some(min), Some(max)) = (min_fee_filter, max_fee_filter) {
+                        pending.set(&self.metrics.peers_min_fee_filter, min);
+                        pending.set(&self.metrics.peers_max_fee_filter, max);
+                    }
+                    if let Some(peer_headers) = max_peer_headers {
+                        pending.set(&self.metrics.peers_max_synced_headers, peer_headers as f64);
+                        if let Some(height) = block_height {
+                            pending.set(&self.metrics.blocks_behind_peers, (peer_headers - height).max(0) as f64);
+                        }
+                    }
+                    pending.reset(&self.metrics.peers_oldest_last_block_seconds);
+                    if let Some(time) = last_block {
+                        let age = (now - time).max(0) as f64;
+                        pending.set_labeled(&self.metrics.peers_oldest_last_block_seconds, &[], age);
+                    }
+                    if config.is_enabled(Section::PeerDetail) {
+                        fresh.insert(Section::PeerDetail);
+                        // Cap the series count so churning peers can't blow up cardinality; the
+                        // busiest peers keep their own series and the rest are summed
+                        let mut by_traffic: Vec<_> = peers.0.iter().collect();
+                        by_traffic.sort_by_key(|p| std::cmp::Reverse(p.bytes_sent + p.bytes_received));
+                        let others = by_traffic.split_off(by_traffic.len().min(config.max_peer_series));
+                        pending.reset(&self.metrics.peer_bytes_sent);
+                        pending.reset(&self.metrics.peer_bytes_received);
+                        for peer in &by_traffic {
+                            pending.set_labeled(&self.metrics.peer_bytes_sent, &[&peer.address], peer.bytes_sent as f64);
+                            pending.set_labeled(&self.metrics.peer_bytes_received, &[&peer.address], peer.bytes_received as f64);
+                        }
+                        if !others.is_empty() {
+                            let sent: u64 = others.iter().map(|p| p.bytes_sent).sum();
+                            let received: u64 = others.iter().map(|p| p.bytes_received).sum();
+                            pending.set_labeled(&self.metrics.peer_bytes_sent, &["others"], sent as f64);
+                            pending.set_labeled(&self.metrics.peer_bytes_received, &["others"], received as f64);
+                        }
+                        pending.set(&self.metrics.peer_series_truncated, if others.is_empty() { 0.0 } else { 1.0 });
+                    }
+                    info!("Updated peer info: peers={} (in={}, out={})", total, inbound, outbound);
+                }
+                Err(e) => {
+                    warn!("Failed to get peer info: {e}");
+                }
             }
         }
 
         // Mining info
-        match self.node.get_mining_info() {
-            Ok(info) => {
-                self.metrics.network_hash_ps.set(info.network_hash_ps);
-                self.metrics.mining_pooled_tx.set(info.pooled_tx as f64);
-                info!("Updated mining info: hashps={}, pooledtx={}", info.network_hash_ps, info.pooled_tx);
-            }
-            Err(e) => {
-                warn!("Failed to get mining info: {e}");
-                had_error = true;
+        if config.is_enabled(Section::Mining) {
+            match report.track("getmininginfo", || self.node.get_mining_info()) {
+                Ok(info) => {
+                    fresh.insert(Section::Mining);
+                    pending.set(&self.metrics.network_hash_ps, info.network_hash_ps);
+                    pending.set(&self.metrics.mining_pooled_tx, info.pooled_tx as f64);
+                    info!("Updated mining info: hashps={}, pooledtx={}", info.network_hash_ps, info.pooled_tx);
+                }
+                Err(e) => {
+                    warn!("Failed to get mining info: {e}");
+                }
             }
         }
 
         // Chain tx stats
-        match self.node.get_chain_tx_stats() {
-            Ok(info) => {
-                self.metrics.chain_tx_count.set(info.tx_count as f64);
-                if let Some(rate) = info.tx_rate {
-                    self.metrics.chain_tx_rate.set(rate);
-                }
-                self.metrics.chain_tx_window_block_count.set(info.window_block_count as f64);
-                if let Some(count) = info.window_tx_count {
-                    self.metrics.chain_tx_window_tx_count.set(count as f64);
+        if collects(Section::ChainTxStats) {
+            match report.track("getchaintxstats", || self.node.get_chain_tx_stats()) {
+                Ok(info) => {
+                    fresh.insert(Section::ChainTxStats);
+                    pending.set(&self.metrics.chain_tx_count, info.tx_count as f64);
+                    if let Some(rate) = info.tx_rate {
+                        pending.set(&self.metrics.chain_tx_rate, rate);
+                    }
+                    pending.set(&self.metrics.chain_tx_window_block_count, info.window_block_count as f64);
+                    if let Some(count) = info.window_tx_count {
+                        pending.set(&self.metrics.chain_tx_window_tx_count, count as f64);
+                    }
+                    if let Some(interval) = info.window_interval {
+                        pending.set(&self.metrics.chain_tx_window_interval, interval as f64);
+                    }
+                    // Both are absent for a window of fewer than two blocks
+                    if let (Some(count), Some(interval)) = (info.window_tx_count, info.window_interval)
+                        && interval > 0
+                    {
+                        pending.set(&self.metrics.chain_tx_per_day, count as f64 * 86_400.0 / interval as f64);
+                    }
+                    info!("Updated chain tx stats: total_txs={}, rate={:?}", info.tx_count, info.tx_rate);
                 }
-                if let Some(interval) = info.window_interval {
-                    self.metrics.chain_tx_window_interval.set(interval as f64);
+                Err(e) => {
+                    warn!("Failed to get chain tx stats: {e}");
                 }
-                info!("Updated chain tx stats: total_txs={}, rate={:?}", info.tx_count, info.tx_rate);
-            }
-            Err(e) => {
-                warn!("Failed to get chain tx stats: {e}");
-                had_error = true;
             }
         }
 
         // Net totals
-        match self.node.get_net_totals() {
-            Ok(info) => {
-                self.metrics.net_total_bytes_received.set(info.total_bytes_received as f64);
-                self.metrics.net_total_bytes_sent.set(info.total_bytes_sent as f64);
-                info!("Updated net totals: recv={}, sent={}", info.total_bytes_received, info.total_bytes_sent);
-            }
-            Err(e) => {
-                warn!("Failed to get net totals: {e}");
-                had_error = true;
+        if config.is_enabled(Section::NetTotals) {
+            match report.track("getnettotals", || self.node.get_net_totals()) {
+                Ok(info) => {
+                    fresh.insert(Section::NetTotals);
+                    pending.set(&self.metrics.net_total_bytes_received, info.total_bytes_received as f64);
+                    pending.set(&self.metrics.net_total_bytes_sent, info.total_bytes_sent as f64);
+                    let current = (info.time_millis, info.total_bytes_received, info.total_bytes_sent);
+                    let previous = self.last_net_totals.lock().expect("net totals lock poisoned").replace(current);
+                    if let Some((time_millis, received, sent)) = previous {
+                        let elapsed = info.time_millis.saturating_sub(time_millis);
+                        if let Some(rate) = bytes_per_second(received, info.total_bytes_received, elapsed) {
+                            pending.set_labeled(&self.metrics.net_bytes_received_per_second, &[], rate);
+                        }
+                        if let Some(rate) = bytes_per_second(sent, info.total_bytes_sent, elapsed) {
+                            pending.set_labeled(&self.metrics.net_bytes_sent_per_second, &[], rate);
+                        }
+                    }
+                    info!("Updated net totals: recv={}, sent={}", info.total_bytes_received, info.total_bytes_sent);
+                }
+                Err(e) => {
+                    warn!("Failed to get net totals: {e}");
+                }
             }
         }
 
         // Fee estimation at various confirmation targets
-        for (target, gauge) in [
-            (2, &self.metrics.fee_estimate_2_blocks),
-            (6, &self.metrics.fee_estimate_6_blocks),
-            (12, &self.metrics.fee_estimate_12_blocks),
-            (144, &self.metrics.fee_estimate_144_blocks),
-        ] {
-            match self.node.estimate_smart_fee(target) {
-                Ok(est) => {
-                    if let Some(rate) = est.fee_rate {
-                        gauge.set(rate);
+        if collects(Section::FeeEstimates) {
+            let mut all_estimated = true;
+            for (target, gauge) in [
+                (2, &self.metrics.fee_estimate_2_blocks),
+                (6, &self.metrics.fee_estimate_6_blocks),
+                (12, &self.metrics.fee_estimate_12_blocks),
+                (144, &self.metrics.fee_estimate_144_blocks),
+            ] {
+                match report.track(&format!("estimatesmartfee/{target}"), || self.node.estimate_smart_fee(target)) {
+                    Ok(est) => {
+                        let label = target.to_string();
+                        if let Some(rate) = est.fee_rate {
+                            pending.set(gauge, rate);
+                            pending.set_labeled(&self.metrics.fee_estimate_last_update, &[&label], now as f64);
+                        }
+                        // A fresh node reports `errors` (insufficient data) instead of a rate; errors
+                        // next to a rate mark a fallback estimate, still a fresh value
+                        let available = est.fee_rate.is_some();
+                        let fallback = available && est.errors.as_ref().is_some_and(|errors| !errors.is_empty());
+                        pending.set_labeled(&self.metrics.fee_estimate_available, &[&label], if available { 1.0 } else { 0.0 });
+                        let fallback_value = if fallback { 1.0 } else { 0.0 };
+                        pending.set_labeled(&self.metrics.fee_estimate_fallback, &[&label], fallback_value);
+                        if target == 2 && available && !fallback {
+                            short_fee_estimate = est.fee_rate;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to estimate smart fee for {target} blocks: {e}");
+                        all_estimated = false;
                     }
-                }
-                Err(e) => {
-                    warn!("Failed to estimate smart fee for {target} blocks: {e}");
-                    had_error = true;
                 }
             }
+            if all_estimated {
+                fresh.insert(Section::FeeEstimates);
+            }
+            info!("Updated fee estimates");
+        }
+
+        // Fee pressure (opt-in), derived from the mempool info and fee estimates above
+        if let Some(fee_pressure) = &config.fee_pressure
+            && let Some((fullness, min_fee, relay_fee)) = mempool_fees
+            && let Some(index) = fee_pressure_index(fee_pressure, fullness, min_fee, relay_fee, short_fee_estimate)
+        {
+            pending.set_labeled(&self.metrics.fee_pressure_index, &[], index);
         }
-        info!("Updated fee estimates");
 
         // Chain tips
-        match self.node.get_chain_tips() {
-            Ok(tips) => {
-                self.metrics.chain_tips_count.set(tips.0.len() as f64);
-                info!("Updated chain tips: count={}", tips.0.len());
-            }
-            Err(e) => {
-                warn!("Failed to get chain tips: {e}");
-                had_error = true;
+        if config.is_enabled(Section::ChainTips) {
+            match report.track("getchaintips", || self.node.get_chain_tips()) {
+                Ok(tips) => {
+                    fresh.insert(Section::ChainTips);
+                    pending.set(&self.metrics.chain_tips_count, tips.0.len() as f64);
+                    info!("Updated chain tips: count={}", tips.0.len());
+                }
+                Err(e) => {
+                    warn!("Failed to get chain tips: {e}");
+                }
             }
         }
 
         // Uptime
-        match self.node.uptime() {
-            Ok(seconds) => {
-                self.metrics.node_uptime_seconds.set(seconds as f64);
-                info!("Updated uptime: {}s", seconds);
-            }
-            Err(e) => {
-                warn!("Failed to get uptime: {e}");
-                had_error = true;
+        if config.is_enabled(Section::Uptime) {
+            match report.track("uptime", || self.node.uptime()) {
+                Ok(seconds) => {
+                    fresh.insert(Section::Uptime);
+                    pending.set(&self.metrics.node_uptime_seconds, seconds as f64);
+                    pending.set(&self.metrics.node_start_timestamp, (now - i64::from(seconds)) as f64);
+                    uptime_seconds = Some(seconds);
+                    info!("Updated uptime: {}s", seconds);
+                }
+                Err(e) => {
+                    warn!("Failed to get uptime: {e}");
+                }
             }
         }
 
-        // Latest block stats (requires block height from blockchain info)
-        if let Some(height) = block_height {
-            match self.node.get_block_stats_by_height(height as u32) {
+        // The genesis block has no stats or coinbase to fetch, which isn't staleness
+        if block_height == Some(0) {
+            fresh.extend([Section::BlockStats, Section::Coinbase]);
+        }
+
+        // Latest block stats (requires block height from blockchain info); the node has
+        // none for the genesis block, e.g. on a fresh regtest chain, so they're not available
+        if collects(Section::BlockStats) && let Some(height) = block_height.filter(|&height| height > 0) {
+            let hash = best_block_hash.clone().unwrap_or_default();
+            let mut last_block_stats = self.last_block_stats.lock().expect("last block stats lock poisoned");
+            let cached = last_block_stats
+                .as_ref()
+                .filter(|(last_hash, fields, _)| *last_hash == hash && *fields == config.block_stats_fields)
+                .map(|(_, _, stats)| stats.clone());
+            let fields = &config.block_stats_fields;
+            let result = match cached {
+                Some(stats) => Ok(stats),
+                None => report
+                    .track("getblockstats", || self.node.get_block_stats_by_height(height as u32, fields))
+                    .inspect(|stats| *last_block_stats = Some((hash, fields.clone(), stats.clone()))),
+            };
+            drop(last_block_stats);
+            match result {
                 Ok(stats) => {
-                    self.metrics.latest_block_txs.set(stats.txs as f64);
-                    self.metrics.latest_block_size.set(stats.total_size as f64);
-                    self.metrics.latest_block_weight.set(stats.total_weight as f64);
-                    self.metrics.latest_block_avg_fee.set(stats.average_fee as f64);
-                    self.metrics.latest_block_avg_fee_rate.set(stats.average_fee_rate as f64);
-                    self.metrics.latest_block_median_fee.set(stats.median_fee as f64);
-                    self.metrics.latest_block_min_fee.set(stats.minimum_fee as f64);
-                    self.metrics.latest_block_max_fee.set(stats.max_fee as f64);
-                    self.metrics.latest_block_min_fee_rate.set(stats.minimum_fee_rate as f64);
-                    self.metrics.latest_block_max_fee_rate.set(stats.max_fee_rate as f64);
-                    self.metrics.latest_block_total_fee.set(stats.total_fee as f64);
-                    self.metrics.latest_block_subsidy.set(stats.subsidy as f64);
-                    self.metrics.latest_block_inputs.set(stats.inputs as f64);
-                    self.metrics.latest_block_outputs.set(stats.outputs as f64);
-                    self.metrics.latest_block_segwit_txs.set(stats.segwit_txs as f64);
-                    self.metrics.latest_block_segwit_total_size.set(stats.segwit_total_size as f64);
-                    self.metrics.latest_block_segwit_total_weight.set(stats.segwit_total_weight as f64);
-                    self.metrics.latest_block_total_out.set(stats.total_out as f64);
-                    self.metrics.latest_block_utxo_increase.set(stats.utxo_increase as f64);
-                    self.metrics.latest_block_fee_rate_10th.set(stats.fee_rate_percentiles[0] as f64);
-                    self.metrics.latest_block_fee_rate_25th.set(stats.fee_rate_percentiles[1] as f64);
-                    self.metrics.latest_block_fee_rate_50th.set(stats.fee_rate_percentiles[2] as f64);
-                    self.metrics.latest_block_fee_rate_75th.set(stats.fee_rate_percentiles[3] as f64);
-                    self.metrics.latest_block_fee_rate_90th.set(stats.fee_rate_percentiles[4] as f64);
-                    info!("Updated latest block stats: height={}, txs={}, total_fee={}", height, stats.txs, stats.total_fee);
+                    fresh.insert(Section::BlockStats);
+                    pending.set(&self.metrics.latest_block_height, height as f64);
+                    pending.set_some(&self.metrics.latest_block_txs, stats.txs.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_size, stats.total_size.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_weight, stats.total_weight.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_avg_fee, stats.average_fee.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_avg_fee_rate, stats.average_fee_rate.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_median_fee, stats.median_fee.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_min_fee, stats.minimum_fee.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_max_fee, stats.max_fee.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_min_fee_rate, stats.minimum_fee_rate.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_max_fee_rate, stats.max_fee_rate.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_total_fee, stats.total_fee.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_subsidy, stats.subsidy.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_inputs, stats.inputs.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_outputs, stats.outputs.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_segwit_txs, stats.segwit_txs.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_segwit_total_size, stats.segwit_total_size.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_segwit_total_weight, stats.segwit_total_weight.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_total_out, stats.total_out.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_utxo_increase, stats.utxo_increase.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_utxo_size_increase, stats.utxo_size_increase.map(|v| v as f64));
+                    // Both `_actual` statistics leave out unspendable outputs
+                    pending.set_some(&self.metrics.latest_block_utxo_increase_actual, stats.utxo_increase_actual.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_utxo_size_increase_actual, stats.utxo_size_increase_actual.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_avg_tx_size, stats.average_tx_size.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_median_tx_size, stats.median_tx_size.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_max_tx_size, stats.max_tx_size.map(|v| v as f64));
+                    pending.set_some(&self.metrics.latest_block_min_tx_size, stats.minimum_tx_size.map(|v| v as f64));
+                    if let Some(percentiles) = stats.fee_rate_percentiles {
+                        pending.set(&self.metrics.latest_block_fee_rate_10th, percentiles[0] as f64);
+                        pending.set(&self.metrics.latest_block_fee_rate_25th, percentiles[1] as f64);
+                        pending.set(&self.metrics.latest_block_fee_rate_50th, percentiles[2] as f64);
+                        pending.set(&self.metrics.latest_block_fee_rate_75th, percentiles[3] as f64);
+                        pending.set(&self.metrics.latest_block_fee_rate_90th, percentiles[4] as f64);
+                    }
+                    info!("Updated latest block stats: height={}, txs={:?}, total_fee={:?}", height, stats.txs, stats.total_fee);
                 }
                 Err(e) => {
                     warn!("Failed to get block stats for height {height}: {e}");
-                    had_error = true;
                 }
             }
         }
 
-        let duration = start.elapsed().as_secs_f64();
-        self.metrics.scrape_duration_seconds.set(duration);
-        self.metrics.scrape_error.set(if had_error { 1.0 } else { 0.0 });
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::Error;
-    use crate::node::{ChainTxStats, MiningInfo};
-    use corepc_client::types::v28::*;
-
-    struct MockNode;
-
-    impl NodeClient for MockNode {
-        fn get_blockchain_info(&self) -> Result<GetBlockchainInfo, Error> {
-            Ok(GetBlockchainInfo {
-                chain: String::from("main"),
-                blocks: 800000,
-                headers: 800000,
-                best_block_hash: String::from(
-                    "0000000000000000000000000000000000000000000000000000000000000000"
-                ),
-                difficulty: 53_911_173_001_054.59,
-                time: 1_700_000_000,
-                median_time: 1_699_999_000,
-                verification_progress: 0.9999,
-                initial_block_download: false,
-                chain_work: String::new(),
-                size_on_disk: 600_000_000_000,
-                pruned: false,
-                prune_height: None,
-                automatic_pruning: None,
-                prune_target_size: None,
-                softforks: Default::default(),
-                warnings: vec![],
-            })
+        // Latest block coinbase (opt-in; requires the best block hash from blockchain info).
+        // The genesis coinbase can't be retrieved, so it's skipped like block stats
+        if collects(Section::Coinbase) && block_height.is_some_and(|height| height > 0)
+            && let Some(hash) = &best_block_hash
+        {
+            let coinbase_txid = match report.track("getblock", || self.node.get_block_txids(hash)) {
+                Ok(block) => block.tx.into_iter().next(),
+                Err(e) => {
+                    warn!("Failed to get block {hash}: {e}");
+                    None
+                }
+            };
+            if let Some(txid) = coinbase_txid {
+                match report.track("getrawtransaction", || self.node.get_raw_transaction(&txid, hash)) {
+                    Ok(tx) => {
+                        fresh.insert(Section::Coinbase);
+                        let value_sat: f64 = tx.vout.iter().map(|output| (output.value * 100_000_000.0).round()).sum();
+                        let pool = tx.vin.first().and_then(|input| input.coinbase.as_deref()).and_then(pool_tag);
+                        pending.set(&self.metrics.latest_block_coinbase_value, value_sat);
+                        pending.reset(&self.metrics.latest_block_pool);
+                        pending.set_labeled(&self.metrics.latest_block_pool, &[pool.unwrap_or("unknown")], 1.0);
+                        info!("Updated latest block coinbase: value={value_sat} sat, pool={pool:?}");
+                    }
+                    Err(e) => {
+                        warn!("Failed to get coinbase transaction {txid}: {e}");
+                    }
+                }
+            }
         }
 
-        fn get_mempool_info(&self) -> Result<GetMempoolInfo, Error> {
-            Ok(GetMempoolInfo {
-                loaded: true,
-                size: 5000,
-                bytes: 3_000_000,
-                usage: 10_000_000,
-                total_fee: 0.5,
-                max_mempool: 300_000_000,
-                mempool_min_fee: 0.00001,
-                min_relay_tx_fee: 0.00001,
-                incremental_relay_fee: 0.00001,
-                unbroadcast_count: 3,
-                full_rbf: false,
-            })
+        // Chainstates (a node without a loaded snapshot reports a single chainstate)
+        if config.is_enabled(Section::Chainstates) {
+            match report.track("getchainstates", || self.node.get_chain_states()) {
+                Ok(states) => {
+                    fresh.insert(Section::Chainstates);
+                    pending.reset(&self.metrics.chainstate_blocks);
+                    pending.reset(&self.metrics.chainstate_validated);
+                    let multiple = states.chain_states.len() > 1;
+                    for state in &states.chain_states {
+                        let label = if state.snapshot_block_hash.is_some() {
+                            "snapshot"
+                        } else if multiple {
+                            "background"
+                        } else {
+                            "normal"
+                        };
+                        pending.set_labeled(&self.metrics.chainstate_blocks, &[label], state.blocks as f64);
+                        pending.set_labeled(&self.metrics.chainstate_validated, &[label], if state.validated { 1.0 } else { 0.0 });
+                    }
+                    info!("Updated chainstates: count={}", states.chain_states.len());
+                }
+                Err(e) => {
+                    warn!("Failed to get chainstates: {e}");
+                }
+            }
         }
 
-        fn get_network_info(&self) -> Result<GetNetworkInfo, Error> {
-            Ok(GetNetworkInfo {
-                version: 250000,
-                subversion: String::from("/Satoshi:25.0.0/"),
-                protocol_version: 70016,
-                local_services: String::new(),
-                local_services_names: vec![],
-                local_relay: true,
-                time_offset: -2,
-                connections: 125,
-                connections_in: 85,
-                connections_out: 40,
-                network_active: true,
-                networks: vec![],
-                relay_fee: 0.00001,
-                incremental_fee: 0.00001,
-                local_addresses: vec![],
-                warnings: vec![],
-            })
+        // Wallets; one that fails (e.g. isn't loaded) is skipped and counted
+        for wallet in &self.wallets {
+            match report.track(&format!("getwalletinfo/{wallet}"), || self.node.get_wallet_info(wallet)) {
+                Ok(info) => {
+                    let labels = [wallet.as_str()];
+                    if let Some(balance) = info.balance {
+                        pending.set_labeled(&self.metrics.wallet_balance, &labels, balance);
+                    }
+                    if let Some(balance) = info.unconfirmed_balance {
+                        pending.set_labeled(&self.metrics.wallet_unconfirmed_balance, &labels, balance);
+                    }
+                    if let Some(balance) = info.immature_balance {
+                        pending.set_labeled(&self.metrics.wallet_immature_balance, &labels, balance);
+                    }
+                    pending.set_labeled(&self.metrics.wallet_tx_count, &labels, info.tx_count as f64);
+                    info!("Updated wallet {wallet}: txcount={}", info.tx_count);
+                }
+                Err(e) => {
+                    warn!("Failed to get wallet info for {wallet}: {e}");
+                    pending.inc_labeled(&self.metrics.wallet_errors, &[wallet]);
+                }
+            }
         }
 
-        fn get_peer_info(&self) -> Result<GetPeerInfo, Error> {
-            Ok(GetPeerInfo(vec![
-                PeerInfo {
-                    id: 1,
-                    address: "1.2.3.4:8333".into(),
-                    address_bind: Some("0.0.0.0:0".into()),
-                    address_local: None,
-                    network: "ipv4".into(),
-                    mapped_as: None,
-                    services: "0000000000000409".into(),
-                    services_names: vec!["NETWORK".into(), "WITNESS".into()],
-                    relay_transactions: true,
-                    last_send: 1_700_000_000,
-                    last_received: 1_700_000_000,
-                    last_transaction: 0,
-                    last_block: 0,
-                    bytes_sent: 50_000,
-                    bytes_received: 100_000,
-                    connection_time: 1_699_900_000,
-                    time_offset: 0,
-                    ping_time: Some(0.05),
-                    minimum_ping: Some(0.02),
-                    ping_wait: None,
-                    version: 70016,
-                    subversion: "/Satoshi:25.0.0/".into(),
-                    inbound: false,
-                    bip152_hb_to: false,
-                    bip152_hb_from: false,
-                    add_node: None,
-                    starting_height: Some(799_990),
-                    presynced_headers: Some(-1),
-                    ban_score: None,
-                    synced_headers: Some(800_000),
-                    synced_blocks: Some(800_000),
-                    inflight: Some(vec![]),
-                    addresses_relay_enabled: None,
-                    addresses_processed: None,
-                    addresses_rate_limited: None,
-                    permissions: vec![],
-                    whitelisted: None,
-                    minimum_fee_filter: 0.00001,
-                    bytes_sent_per_message: Default::default(),
-                    bytes_received_per_message: Default::default(),
-                    connection_type: Some("outbound-full-relay".into()),
-                    transport_protocol_type: "v1".into(),
-                    session_id: String::new(),
-                },
-                PeerInfo {
-                    id: 2,
-                    address: "5.6.7.8:8333".into(),
-                    address_bind: Some("0.0.0.0:0".into()),
-                    address_local: None,
-                    network: "ipv4".into(),
-                    mapped_as: None,
-                    services: "0000000000000409".into(),
-                    services_names: vec!["NETWORK".into(), "WITNESS".into()],
-                    relay_transactions: true,
-                    last_send: 1_700_000_000,
-                    last_received: 1_700_000_000,
-                    last_transaction: 0,
-                    last_block: 0,
-                    bytes_sent: 30_000,
-                    bytes_received: 60_000,
-                    connection_time: 1_699_900_000,
-                    time_offset: 0,
-                    ping_time: Some(0.10),
-                    minimum_ping: Some(0.05),
-                    ping_wait: None,
-                    version: 70016,
-                    subversion: "/Satoshi:25.0.0/".into(),
-                    inbound: true,
-                    bip152_hb_to: false,
-                    bip152_hb_from: false,
-                    add_node: None,
-                    starting_height: Some(799_990),
-                    presynced_headers: Some(-1),
-                    ban_score: None,
-                    synced_headers: Some(800_000),
-                    synced_blocks: Some(800_000),
-                    inflight: Some(vec![]),
-                    addresses_relay_enabled: None,
-                    addresses_processed: None,
-                    addresses_rate_limited: None,
-                    permissions: vec![],
-                    whitelisted: None,
-                    minimum_fee_filter: 0.00001,
-                    bytes_sent_per_message: Default::default(),
-                    bytes_received_per_message: Default::default(),
-                    connection_type: Some("inbound".into()),
-                    transport_protocol_type: "v1".into(),
-                    session_id: String::new(),
-                },
-            ]))
-        }
-
-        fn get_mining_info(&self) -> Result<MiningInfo, Error> {
-            Ok(MiningInfo {
-                blocks: 800_000,
-                current_block_weight: Some(3_993_000),
-                current_block_tx: Some(2_500),
-                difficulty: 53_911_173_001_054.59,
-                network_hash_ps: 4.5e17,
-                pooled_tx: 5000,
-                chain: "main".into(),
-                warnings: vec![],
-            })
+        if config.is_enabled(Section::Warnings) && let Some(warnings) = warnings {
+            fresh.insert(Section::Warnings);
+            pending.set(&self.metrics.warnings_active, if warnings.is_empty() { 0.0 } else { 1.0 });
+            pending.reset(&self.metrics.warning_info);
+            for warning in &warnings {
+                pending.set_labeled(&self.metrics.warning_info, &[warning], 1.0);
+            }
         }
 
-        fn get_chain_tx_stats(&self) -> Result<ChainTxStats, Error> {
-            Ok(ChainTxStats {
-                time: 1_700_000_000,
-                tx_count: 900_000_000,
-                window_final_block_hash: "0000000000000000000000000000000000000000000000000000000000000000".into(),
-                window_final_block_height: 800_000,
-                window_block_count: 4032,
-                window_tx_count: Some(12_000_000),
-                window_interval: Some(2_419_200),
-                tx_rate: Some(4.96),
-            })
+        // Extra metrics; an RPC shared by several of them is called once per collection
+        let mut responses: BTreeMap<String, Result<serde_json::Value, String>> = BTreeMap::new();
+        for extra in &self.metrics.extra {
+            let config = &extra.config;
+            let call = if config.params.is_empty() {
+                config.rpc_method.clone()
+            } else {
+                format!("{} {}", config.rpc_method, serde_json::Value::from(config.params.clone()))
+            };
+            let response = responses.entry(call.clone()).or_insert_with(|| {
+                report.track(&call, || self.node.call_json(&config.rpc_method, &config.params)).map_err(|e| e.to_string())
+            });
+            let value = match response {
+                Ok(response) => response
+                    .pointer(&config.json_pointer)
+                    .and_then(|v| v.as_f64().or_else(|| v.as_bool().map(|b| if b { 1.0 } else { 0.0 })))
+                    .ok_or_else(|| format!("no number at {:?} in the {call} result", config.json_pointer)),
+                Err(e) => Err(e.clone()),
+            };
+            match value {
+                Ok(value) => pending.set(&extra.gauge, value),
+                Err(e) => {
+                    warn!("Failed to read extra metric {}: {e}", config.name);
+                    pending.inc_labeled(&self.metrics.extra_metric_errors, &[&config.name]);
+                }
+            }
         }
 
-        fn get_net_totals(&self) -> Result<GetNetTotals, Error> {
-            Ok(GetNetTotals {
-                total_bytes_received: 5_000_000_000,
-                total_bytes_sent: 3_000_000_000,
-                time_millis: 1_700_000_000_000,
-                upload_target: UploadTarget {
-                    timeframe: 86400,
-                    target: 0,
-                    target_reached: false,
-                    serve_historical_blocks: true,
-                    bytes_left_in_cycle: 0,
-                    time_left_in_cycle: 43200,
-                },
-            })
+        // Enabled sections that failed, or were skipped for want of blockchain info, keep
+        // their previous values; flag those per section. Sections skipped during IBD are
+        // left out rather than flagged, so a days-long IBD doesn't keep staleness alerts firing
+        pending.reset(&self.metrics.section_stale);
+        for section in config.enabled_sections().filter(|&section| collects(section)) {
+            let stale = if fresh.contains(&section) { 0.0 } else { 1.0 };
+            pending.set_labeled(&self.metrics.section_stale, &[&section.to_string()], stale);
         }
 
-        fn estimate_smart_fee(&self, conf_target: u32) -> Result<EstimateSmartFee, Error> {
-            let rate = match conf_target {
-                2 => 0.00025,
-                6 => 0.00015,
-                12 => 0.00010,
-                144 => 0.00005,
-                _ => 0.00010,
-            };
-            Ok(EstimateSmartFee {
-                fee_rate: Some(rate),
-                errors: None,
-                blocks: conf_target,
-            })
+        // One series per method that failed and hasn't succeeded since, so varying messages
+        // replace each other instead of accumulating
+        {
+            let mut last_errors = self.last_errors.lock().expect("last errors lock poisoned");
+            for (method, outcome) in &report.rpcs {
+                match &outcome.error {
+                    Some(error) => last_errors.insert(method.clone(), error_label(error)),
+                    None => last_errors.remove(method),
+                };
+            }
+            pending.reset(&self.metrics.last_error_info);
+            for (method, error) in last_errors.iter() {
+                pending.set_labeled(&self.metrics.last_error_info, &[method, error], 1.0);
+            }
         }
 
-        fn get_chain_tips(&self) -> Result<GetChainTips, Error> {
-            Ok(GetChainTips(vec![
-                ChainTips {
-                    height: 800_000,
-                    hash: "0000000000000000000000000000000000000000000000000000000000000000".into(),
-                    branch_length: 0,
-                    status: ChainTipsStatus::Active,
-                },
-                ChainTips {
-                    height: 799_998,
-                    hash: "0000000000000000000000000000000000000000000000000000000000000001".into(),
-                    branch_length: 2,
-                    status: ChainTipsStatus::ValidFork,
-                },
-            ]))
-        }
-
-        fn uptime(&self) -> Result<u32, Error> {
-            Ok(86400)
-        }
-
-        fn get_block_stats_by_height(&self, _height: u32) -> Result<GetBlockStats, Error> {
-            Ok(GetBlockStats {
-                average_fee: 15_000,
-                average_fee_rate: 25,
-                average_tx_size: 500,
-                block_hash: "0000000000000000000000000000000000000000000000000000000000000000".into(),
-                fee_rate_percentiles: [5, 10, 20, 50, 100],
-                height: 800_000,
-                inputs: 6000,
-                max_fee: 500_000,
-                max_fee_rate: 200,
-                max_tx_size: 100_000,
-                median_fee: 10_000,
-                median_time: 1_699_999_000,
-                median_tx_size: 250,
-                minimum_fee: 500,
-                minimum_fee_rate: 1,
-                minimum_tx_size: 150,
-                outputs: 8000,
-                subsidy: 625_000_000,
-                segwit_total_size: 1_500_000,
-                segwit_total_weight: 3_000_000,
-                segwit_txs: 2000,
-                time: 1_700_000_000,
-                total_out: 500_000_000_000,
-                total_size: 2_000_000,
-                total_weight: 3_993_000,
-                total_fee: 37_500_000,
-                txs: 2500,
-                utxo_increase: 500,
-                utxo_size_increase: 25_000,
-                utxo_increase_actual: None,
-                utxo_size_increase_actual: None,
-            })
+        // Sections that failed staged nothing, so they keep their last good values;
+        // on a total outage the previous snapshot is served unchanged.
+        pending.commit();
+        *self.status.lock().expect("status lock poisoned") =
+            Some((Instant::now(), NodeStatus::new(blockchain_status, uptime_seconds)));
+
+        let duration = start.elapsed().as_secs_f64();
+        self.metrics.scrape_duration_seconds.set(duration);
+        self.metrics.scrape_duration_histogram.observe(duration);
+        // Outside of any span, e.g. without a subscriber, there's no trace to point to
+        if let Some(span) = tracing::Span::current().id() {
+            self.record_exemplar(span.into_u64(), duration);
         }
+        self.metrics.scrape_error.set(if report.had_error() { 1.0 } else { 0.0 });
+        // Connection failures say nothing about the credentials, so they keep the last verdict
+        if let Some(auth_ok) = report.auth_ok() {
+            self.metrics.node_rpc_auth_ok.with_label_values::<&str>(&[]).set(if auth_ok { 1.0 } else { 0.0 });
+        }
+
+        report
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExtraMetricConfig;
+    use crate::mock::MockNodeClient;
+    use crate::node::{BlockHeader, WalletInfo};
+    use prometheus::core::Collector;
 
     #[test]
     fn test_collect_updates_gauges() {
         let metrics = BitcoinMetrics::new().unwrap();
-        let collector = MetricsCollector::new(MockNode, metrics);
+        let collector = MetricsCollector::new(MockNodeClient::new(), metrics);
 
         collector.collect();
 
         // Blockchain info
         assert_eq!(collector.metrics().blocks.get(), 800_000.0);
         assert_eq!(collector.metrics().headers.get(), 800_000.0);
+        assert_eq!(collector.metrics().headers_blocks_gap.get(), 0.0);
         assert!(collector.metrics().difficulty.get() > 0.0);
+        assert!((collector.metrics().difficulty_log2.get() - 45.615).abs() < 0.001);
         assert_eq!(collector.metrics().initial_block_download.get(), 0.0);
         assert_eq!(collector.metrics().chain_pruned.get(), 0.0);
+        assert_eq!(collector.metrics().node_synced.get(), 1.0);
         assert_eq!(collector.metrics().size_on_disk.get(), 600_000_000_000.0);
+        assert_eq!(collector.metrics().best_block_timestamp.get(), 1_700_000_000.0);
+        assert!((collector.metrics().chain_work_log2.get() - 94.36975642920993).abs() < 1e-9);
 
         // Mempool info
         assert_eq!(collector.metrics().mempool_transactions.get(), 5000.0);
         assert_eq!(collector.metrics().mempool_bytes.get(), 3_000_000.0);
         assert_eq!(collector.metrics().mempool_total_fee.get(), 0.5);
+        assert_eq!(collector.metrics().mempool_total_fee_sat.get(), 50_000_000.0);
         assert_eq!(collector.metrics().mempool_unbroadcast_count.get(), 3.0);
         assert_eq!(collector.metrics().mempool_full_rbf.get(), 0.0);
 
@@ -554,18 +1252,37 @@ mod tests {
         assert_eq!(collector.metrics().connections_in.get(), 85.0);
         assert_eq!(collector.metrics().connections_out.get(), 40.0);
         assert_eq!(collector.metrics().network_active.get(), 1.0);
+        assert_eq!(collector.metrics().accepting_inbound.get(), 1.0);
+        assert_eq!(collector.metrics().local_addresses_count.get(), 1.0);
         assert_eq!(collector.metrics().protocol_version.get(), 70016.0);
+        assert_eq!(collector.metrics().core_version_supported.get(), 1.0);
         assert_eq!(collector.metrics().time_offset.get(), -2.0);
         assert_eq!(collector.metrics().relay_fee.get(), 0.00001);
         assert_eq!(collector.metrics().incremental_fee.get(), 0.00001);
+        assert_eq!(collector.metrics().relay_fee_sat_per_vb.get(), 1.0);
+        assert_eq!(collector.metrics().incremental_fee_sat_per_vb.get(), 1.0);
+        assert_eq!(collector.metrics().network_reachable.with_label_values(&["ipv4"]).get(), 1.0);
+        assert_eq!(collector.metrics().network_reachable.with_label_values(&["onion"]).get(), 0.0);
+        assert_eq!(collector.metrics().network_proxy_configured.with_label_values(&["onion"]).get(), 0.0);
 
         // Peer info
         assert_eq!(collector.metrics().peer_count.get(), 2.0);
         assert_eq!(collector.metrics().peers_inbound.get(), 1.0);
         assert_eq!(collector.metrics().peers_outbound.get(), 1.0);
+        assert_eq!(collector.metrics().outbound_block_relay_connections.get(), 0.0);
         assert_eq!(collector.metrics().peers_total_bytes_sent.get(), 80_000.0);
         assert_eq!(collector.metrics().peers_total_bytes_received.get(), 160_000.0);
         assert!((collector.metrics().peers_avg_ping_seconds.get() - 0.075).abs() < 0.001);
+        assert!((collector.metrics().peers_avg_min_ping_seconds.get() - 0.035).abs() < 0.001);
+        assert_eq!(collector.metrics().peers_worst_min_ping_seconds.get(), 0.05);
+        assert_eq!(collector.metrics().peers_min_fee_filter.get(), 0.00001);
+        assert_eq!(collector.metrics().peers_max_fee_filter.get(), 0.0001);
+        assert_eq!(collector.metrics().peers_max_synced_headers.get(), 800_002.0);
+        assert_eq!(collector.metrics().blocks_behind_peers.get(), 2.0);
+        assert_eq!(collector.metrics().peers_addresses_processed.get(), 1_500.0);
+        assert_eq!(collector.metrics().peers_addresses_rate_limited.get(), 30.0);
+        assert_eq!(collector.metrics().peers_by_transport.with_label_values(&["v1"]).get(), 1.0);
+        assert_eq!(collector.metrics().peers_by_transport.with_label_values(&["v2"]).get(), 1.0);
 
         // Mining info
         assert_eq!(collector.metrics().network_hash_ps.get(), 4.5e17);
@@ -577,6 +1294,8 @@ mod tests {
         assert_eq!(collector.metrics().chain_tx_window_block_count.get(), 4032.0);
         assert_eq!(collector.metrics().chain_tx_window_tx_count.get(), 12_000_000.0);
         assert_eq!(collector.metrics().chain_tx_window_interval.get(), 2_419_200.0);
+        // 12M transactions over 28 days
+        assert!((collector.metrics().chain_tx_per_day.get() - 12_000_000.0 / 28.0).abs() < 1e-6);
 
         // Net totals
         assert_eq!(collector.metrics().net_total_bytes_received.get(), 5_000_000_000.0);
@@ -593,8 +1312,11 @@ mod tests {
 
         // Uptime
         assert_eq!(collector.metrics().node_uptime_seconds.get(), 86400.0);
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64() - 86400.0;
+        assert!((collector.metrics().node_start_timestamp.get() - started).abs() <= 2.0);
 
         // Latest block stats
+        assert_eq!(collector.metrics().latest_block_height.get(), 800_000.0);
         assert_eq!(collector.metrics().latest_block_txs.get(), 2500.0);
         assert_eq!(collector.metrics().latest_block_size.get(), 2_000_000.0);
         assert_eq!(collector.metrics().latest_block_weight.get(), 3_993_000.0);
@@ -612,74 +1334,998 @@ mod tests {
         assert_eq!(collector.metrics().latest_block_segwit_txs.get(), 2000.0);
         assert_eq!(collector.metrics().latest_block_total_out.get(), 500_000_000_000.0);
         assert_eq!(collector.metrics().latest_block_utxo_increase.get(), 500.0);
+        assert_eq!(collector.metrics().latest_block_utxo_size_increase.get(), 40_000.0);
+        assert_eq!(collector.metrics().latest_block_utxo_increase_actual.get(), 490.0);
+        assert_eq!(collector.metrics().latest_block_utxo_size_increase_actual.get(), 39_200.0);
+        assert_eq!(collector.metrics().latest_block_avg_tx_size.get(), 400.0);
+        assert_eq!(collector.metrics().latest_block_median_tx_size.get(), 250.0);
+        assert_eq!(collector.metrics().latest_block_max_tx_size.get(), 90_000.0);
+        assert_eq!(collector.metrics().latest_block_min_tx_size.get(), 150.0);
         assert_eq!(collector.metrics().latest_block_fee_rate_10th.get(), 5.0);
         assert_eq!(collector.metrics().latest_block_fee_rate_25th.get(), 10.0);
         assert_eq!(collector.metrics().latest_block_fee_rate_50th.get(), 20.0);
         assert_eq!(collector.metrics().latest_block_fee_rate_75th.get(), 50.0);
         assert_eq!(collector.metrics().latest_block_fee_rate_90th.get(), 100.0);
 
+        // Chainstates
+        assert_eq!(collector.metrics().chainstate_blocks.with_label_values(&["background"]).get(), 400_000.0);
+        assert_eq!(collector.metrics().chainstate_blocks.with_label_values(&["snapshot"]).get(), 800_000.0);
+        assert_eq!(collector.metrics().chainstate_validated.with_label_values(&["snapshot"]).get(), 0.0);
+
+        // Warnings
+        assert_eq!(collector.metrics().warnings_active.get(), 1.0);
+        assert_eq!(
+            collector.metrics().warning_info.with_label_values(&["This is a pre-release test build - use at your own risk"]).get(),
+            1.0
+        );
+
         // Meta
         assert_eq!(collector.metrics().scrape_error.get(), 0.0);
+        assert_eq!(collector.metrics().seconds_since_last_scrape.get(), 0.0);
+        assert_eq!(collector.metrics().scrape_duration_histogram.get_sample_count(), 1);
+        collector.collect();
+        assert!(collector.metrics().seconds_since_last_scrape.get() > 0.0);
+        assert_eq!(collector.metrics().scrape_duration_histogram.get_sample_count(), 2);
     }
 
-    struct PartialFailNode;
-
-    impl NodeClient for PartialFailNode {
-        fn get_blockchain_info(&self) -> Result<GetBlockchainInfo, Error> {
-            MockNode.get_blockchain_info()
-        }
+    #[test]
+    fn test_mempool_detail_is_opt_in() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        let report = collector.collect();
+        assert!(!report.rpcs.contains_key("getrawmempool"));
+        assert_eq!(collector.metrics().mempool_tx_vsize_p50.get(), 0.0);
 
-        fn get_mempool_info(&self) -> Result<GetMempoolInfo, Error> {
-            Err(Error::Config("simulated failure".to_string()))
-        }
+        let config = CollectorConfig { mempool_detail: true, ..Default::default() };
+        let collector = MetricsCollector::with_config(MockNodeClient::new(), BitcoinMetrics::new().unwrap(), config);
+        collector.collect();
+        assert_eq!(collector.metrics().mempool_tx_vsize_p50.get(), 200.0);
+        assert_eq!(collector.metrics().mempool_tx_vsize_p90.get(), 1_000.0);
+        assert_eq!(collector.metrics().mempool_tx_vsize_p99.get(), 50_000.0);
+        assert_eq!(collector.metrics().mempool_oldest_unbroadcast_seconds.get(), 0.0);
+        assert_eq!(collector.metrics().scrape_error.get(), 0.0);
+    }
 
-        fn get_network_info(&self) -> Result<GetNetworkInfo, Error> {
-            MockNode.get_network_info()
-        }
+    #[test]
+    fn test_mempool_oldest_unbroadcast() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let node = MockNodeClient::new().with_raw_mempool(|mempool| {
+            mempool.0.insert("aa".into(), crate::mock::mempool_entry(200, now - 600, true));
+            mempool.0.insert("bb".into(), crate::mock::mempool_entry(200, now - 60, true));
+        });
+        let config = CollectorConfig { mempool_detail: true, ..Default::default() };
+        let collector = MetricsCollector::with_config(node, BitcoinMetrics::new().unwrap(), config);
+        collector.collect();
+        assert!((collector.metrics().mempool_oldest_unbroadcast_seconds.get() - 600.0).abs() <= 2.0);
+    }
 
-        fn get_peer_info(&self) -> Result<GetPeerInfo, Error> {
-            MockNode.get_peer_info()
-        }
+    #[test]
+    fn test_disabled_sections_are_neither_fetched_nor_exported() {
+        let config = CollectorConfig {
+            disabled_sections: [Section::Mining, Section::FeeEstimates].into(),
+            ..Default::default()
+        };
+        let metrics = BitcoinMetrics::builder().sections(config.enabled_sections()).build().unwrap();
+        let collector = MetricsCollector::with_config(MockNodeClient::new(), metrics, config);
 
-        fn get_mining_info(&self) -> Result<MiningInfo, Error> {
-            MockNode.get_mining_info()
-        }
+        let report = collector.collect();
+        assert!(!report.rpcs.contains_key("getmininginfo"));
+        assert!(!report.rpcs.keys().any(|method| method.starts_with("estimatesmartfee")));
+        assert!(report.rpcs.contains_key("getblockchaininfo"));
 
-        fn get_chain_tx_stats(&self) -> Result<ChainTxStats, Error> {
-            MockNode.get_chain_tx_stats()
-        }
+        let names: Vec<String> = collector.metrics().registry.gather().iter().map(|f| f.name().to_string()).collect();
+        assert!(!names.iter().any(|name| name == "bitcoin_network_hash_per_second" || name.starts_with("bitcoin_fee_estimate")));
+        assert!(names.iter().any(|name| name == "bitcoin_blocks"));
+        assert!(names.iter().any(|name| name == "bitcoin_collector_last_scrape_error"));
+    }
 
-        fn get_net_totals(&self) -> Result<GetNetTotals, Error> {
-            MockNode.get_net_totals()
-        }
+    #[test]
+    fn test_series_count_matches_scrape() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        let service = crate::MetricsService::new(collector);
 
-        fn estimate_smart_fee(&self, conf_target: u32) -> Result<EstimateSmartFee, Error> {
-            MockNode.estimate_smart_fee(conf_target)
-        }
+        let text = service.scrape();
 
-        fn get_chain_tips(&self) -> Result<GetChainTips, Error> {
-            MockNode.get_chain_tips()
-        }
+        let series = text.lines().filter(|line| !line.starts_with('#')).count();
+        let reported = text.lines().find_map(|line| line.strip_prefix("bitcoin_exporter_series_count ")).unwrap();
+        assert_eq!(reported.parse::<usize>().unwrap(), series);
+    }
 
-        fn uptime(&self) -> Result<u32, Error> {
-            MockNode.uptime()
-        }
+    #[test]
+    fn test_scrape_checked_reports_errors() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        let service = crate::MetricsService::new(collector);
+        assert!(!service.scrape_checked().1);
+
+        let node = MockNodeClient::new().with_error("getpeerinfo", "unavailable");
+        let service = crate::MetricsService::new(MetricsCollector::new(node, BitcoinMetrics::new().unwrap()));
+        let (body, had_error) = service.scrape_checked();
+        assert!(had_error);
+        assert!(body.contains("bitcoin_blocks 800000"));
+    }
+
+    #[test]
+    fn test_history_keeps_latest_reports() {
+        let config = CollectorConfig { report_history_size: 2, ..Default::default() };
+        let node = MockNodeClient::new().with_error("getmininginfo", "unavailable");
+        let collector = MetricsCollector::with_config(node, BitcoinMetrics::new().unwrap(), config);
+        let service = crate::MetricsService::new(collector);
+        assert!(service.history().is_empty());
 
-        fn get_block_stats_by_height(&self, height: u32) -> Result<GetBlockStats, Error> {
-            MockNode.get_block_stats_by_height(height)
+        for _ in 0..3 {
+            service.collect();
         }
+        let history = service.history();
+        assert_eq!(history.len(), 2);
+        assert!(!history[1].timed_out);
+        assert!(history[1].rpcs.rpcs["getblockchaininfo"].ok);
+        assert!(!history[1].rpcs.rpcs["getmininginfo"].ok);
+
+        service.reload(&CollectorConfig { report_history_size: 0, ..Default::default() }).unwrap();
+        service.collect();
+        assert!(service.history().is_empty());
     }
 
     #[test]
-    fn test_partial_failure_sets_error_gauge() {
+    fn test_reload_switches_sections() {
+        let config = CollectorConfig { disabled_sections: [Section::Mining].into(), ..Default::default() };
+        let metrics = BitcoinMetrics::builder().sections(config.enabled_sections()).build().unwrap();
+        let service = crate::MetricsService::new(MetricsCollector::with_config(MockNodeClient::new(), metrics, config));
+        let exported = |service: &crate::MetricsService<MockNodeClient>, name: &str| {
+            service.gather().iter().any(|family| family.name() == name)
+        };
+        assert!(!exported(&service, "bitcoin_network_hash_per_second"));
+        assert!(exported(&service, "bitcoin_peer_count"));
+
+        let reloaded = CollectorConfig { disabled_sections: [Section::Peers].into(), ..Default::default() };
+        service.reload(&reloaded).unwrap();
+
+        assert!(exported(&service, "bitcoin_network_hash_per_second"));
+        assert!(!exported(&service, "bitcoin_peer_count"));
+        // Reapplying the same sections registers nothing twice
+        service.reload(&reloaded).unwrap();
+    }
+
+    #[test]
+    fn test_block_stats_fields_leave_unrequested_gauges_unset() {
+        let config = CollectorConfig { block_stats_fields: vec!["feerate_percentiles".to_string()], ..Default::default() };
+        let collector = MetricsCollector::with_config(MockNodeClient::new(), BitcoinMetrics::new().unwrap(), config);
+
+        collector.collect();
+
+        assert_eq!(collector.metrics().latest_block_fee_rate_50th.get(), 20.0);
+        assert_eq!(collector.metrics().latest_block_txs.get(), 0.0);
+        assert_eq!(collector.metrics().latest_block_total_fee.get(), 0.0);
+        assert_eq!(collector.metrics().scrape_error.get(), 0.0);
+    }
+
+    #[test]
+    fn test_fee_estimate_unavailable_keeps_stale_rate() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().fee_estimate_available.with_label_values(&["2"]).get(), 1.0);
+        let updated = collector.metrics().fee_estimate_last_update.with_label_values(&["2"]).get();
+        assert!(updated > 0.0);
+        collector.metrics().fee_estimate_last_update.with_label_values(&["2"]).set(1.0);
+
+        let collector = MetricsCollector { node: MockNodeClient::new().with_fee_rate(2, None), ..collector };
+        collector.collect();
+        // The stale rate keeps its last update time, the fresh ones move forward
+        assert_eq!(collector.metrics().fee_estimate_last_update.with_label_values(&["2"]).get(), 1.0);
+        assert!(collector.metrics().fee_estimate_last_update.with_label_values(&["6"]).get() >= updated);
+
+        assert_eq!(collector.metrics().fee_estimate_2_blocks.get(), 0.00025);
+        assert_eq!(collector.metrics().fee_estimate_available.with_label_values(&["2"]).get(), 0.0);
+        assert_eq!(collector.metrics().fee_estimate_available.with_label_values(&["6"]).get(), 1.0);
+    }
+
+    #[test]
+    fn test_fee_estimate_fallback() {
+        let node = MockNodeClient::new().with_fee_rate(6, Some(0.0002)).with_fee_estimate_error(6, "Fallback fee used");
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+
+        collector.collect();
+
+        // The best-guess rate is still exported, and flagged
+        assert_eq!(collector.metrics().fee_estimate_6_blocks.get(), 0.0002);
+        assert_eq!(collector.metrics().fee_estimate_available.with_label_values(&["6"]).get(), 1.0);
+        assert_eq!(collector.metrics().fee_estimate_fallback.with_label_values(&["6"]).get(), 1.0);
+        assert_eq!(collector.metrics().fee_estimate_fallback.with_label_values(&["2"]).get(), 0.0);
+        // Without a rate there's nothing to fall back to
+        let collector = MetricsCollector { node: MockNodeClient::new().with_fee_rate(6, None), ..collector };
+        collector.collect();
+        assert_eq!(collector.metrics().fee_estimate_fallback.with_label_values(&["6"]).get(), 0.0);
+    }
+
+    #[test]
+    fn test_connections_saturation_needs_max_connections() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert!(!collector.metrics().registry.gather().iter().any(|f| f.name() == "bitcoin_connections_saturation_ratio"));
+
+        let collector = collector.with_max_connections(Some(250));
+        collector.collect();
+        assert_eq!(collector.metrics().connections_saturation_ratio.with_label_values::<&str>(&[]).get(), 0.5);
+    }
+
+    #[test]
+    fn test_chain_mismatch() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert!(!collector.metrics().registry.gather().iter().any(|f| f.name() == "bitcoin_chain_mismatch"));
+
+        let collector = collector.with_expected_chain(Some("main".to_string()));
+        collector.collect();
+        assert_eq!(collector.metrics().chain_mismatch.with_label_values::<&str>(&[]).get(), 0.0);
+
+        let collector = collector.with_expected_chain(Some("test".to_string()));
+        collector.collect();
+        assert_eq!(collector.metrics().chain_mismatch.with_label_values::<&str>(&[]).get(), 1.0);
+    }
+
+    #[test]
+    fn test_coinbase_detail_is_opt_in() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        let report = collector.collect();
+        assert!(!report.rpcs.contains_key("getrawtransaction"));
+
+        let config = CollectorConfig { coinbase_detail: true, ..Default::default() };
+        let collector = MetricsCollector::with_config(MockNodeClient::new(), BitcoinMetrics::new().unwrap(), config);
+        collector.collect();
+        assert_eq!(collector.metrics().latest_block_coinbase_value.get(), 662_500_000.0);
+        assert_eq!(collector.metrics().latest_block_pool.with_label_values(&["Foundry USA"]).get(), 1.0);
+
+        let collector = MetricsCollector { node: collector.node.with_coinbase(|tx| tx.vin[0].coinbase = None), ..collector };
+        collector.collect();
+        // The previous block's pool is cleared rather than left at 1
+        let pools = collector.metrics().latest_block_pool.collect();
+        assert_eq!(pools[0].get_metric().len(), 1);
+        assert_eq!(collector.metrics().latest_block_pool.with_label_values(&["unknown"]).get(), 1.0);
+    }
+
+    #[test]
+    fn test_pool_tag() {
+        assert_eq!(pool_tag("0300350c2f566961425443"), Some("ViaBTC"));
+        assert_eq!(pool_tag("0300350c"), None);
+        assert_eq!(pool_tag("zz"), None);
+    }
+
+    #[test]
+    fn test_hex_log2() {
+        assert_eq!(hex_log2("0000000000000000000000000000000000000000000000000000000000000100"), Some(8.0));
+        assert_eq!(hex_log2("1"), Some(0.0));
+        assert_eq!(hex_log2(""), None);
+        assert_eq!(hex_log2("0000"), None);
+        assert_eq!(hex_log2("not hex"), None);
+    }
+
+    #[test]
+    fn test_total_failure_keeps_last_good_snapshot() {
         let metrics = BitcoinMetrics::new().unwrap();
-        let collector = MetricsCollector::new(PartialFailNode, metrics);
+        let collector = MetricsCollector::new(MockNodeClient::new(), metrics);
+
+        collector.collect();
+        assert_eq!(collector.metrics().scrape_error.get(), 0.0);
+
+        collector.node.set_outage(true);
+        collector.collect();
+
+        // Every RPC failed, so the previous snapshot is served with the error flag set
+        assert_eq!(collector.metrics().blocks.get(), 800_000.0);
+        assert_eq!(collector.metrics().mempool_transactions.get(), 5000.0);
+        assert_eq!(collector.metrics().peer_count.get(), 2.0);
+        assert_eq!(collector.metrics().latest_block_txs.get(), 2500.0);
+        assert_eq!(collector.metrics().chainstate_blocks.with_label_values(&["snapshot"]).get(), 800_000.0);
+        assert_eq!(collector.metrics().scrape_error.get(), 1.0);
+    }
+
+    #[test]
+    fn test_node_rpc_auth_ok() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        let auth_ok = || {
+            let families = collector.metrics().node_rpc_auth_ok.collect();
+            families[0].get_metric().first().map(|metric| metric.get_gauge().value())
+        };
+        assert_eq!(auth_ok(), None);
+
+        collector.collect();
+        assert_eq!(auth_ok(), Some(1.0));
+
+        collector.node.set_unauthorized(true);
+        collector.collect();
+        assert_eq!(auth_ok(), Some(0.0));
+        assert_eq!(collector.metrics().scrape_error.get(), 1.0);
+
+        // An unreachable node leaves the credentials' last verdict alone
+        collector.node.set_unauthorized(false);
+        collector.node.set_outage(true);
+        collector.collect();
+        assert_eq!(auth_ok(), Some(0.0));
+
+        collector.node.set_outage(false);
+        collector.collect();
+        assert_eq!(auth_ok(), Some(1.0));
+    }
+
+    #[test]
+    fn test_scrape_duration_exemplars() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert!(collector.scrape_duration_exemplars().is_empty());
+
+        // The service collects on another thread, which has to inherit the span
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            let span = tracing::info_span!("scrape");
+            let service = crate::MetricsService::new(collector);
+            let (text, _) = span.in_scope(|| service.scrape_openmetrics());
+            let span_id = span.id().unwrap().into_u64();
+            assert!(text.contains(&format!("# {{span_id=\"{span_id:016x}\"}}")), "{text}");
+            assert!(text.ends_with("# EOF\n"));
+        });
+    }
+
+    #[test]
+    fn test_status_reuses_last_collection() {
+        let metrics = BitcoinMetrics::new().unwrap();
+        let collector = MetricsCollector::new(MockNodeClient::new(), metrics);
+
+        collector.collect();
+        let status = collector.status();
+        assert_eq!(status.status, "ok");
+        assert_eq!(status.blocks, Some(800_000));
+        assert_eq!(status.ibd, Some(false));
+        assert_eq!(status.uptime_seconds, Some(86400));
+
+        // A failed collection marks the node unreachable, even though metrics keep their values
+        collector.node.set_outage(true);
+        collector.collect();
+        let status = collector.status();
+        assert_eq!(status.status, "unreachable");
+        assert!(!status.node_reachable);
+        assert_eq!(status.blocks, None);
+    }
+
+    #[test]
+    fn test_seconds_since_synced() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().seconds_since_synced.get(), 0.0);
+
+        *collector.synced_since.lock().unwrap() = Some(Instant::now() - Duration::from_secs(60));
+        collector.collect();
+        assert!(collector.metrics().seconds_since_synced.get() >= 60.0);
+
+        // Re-entering IBD (a reindex) starts the count over
+        let node = MockNodeClient::new().with_blockchain_info(|info| info.initial_block_download = true);
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        assert_eq!(collector.metrics().seconds_since_synced.get(), 0.0);
+        let collector = MetricsCollector { node: MockNodeClient::new(), ..collector };
+        collector.collect();
+        assert!(collector.metrics().seconds_since_synced.get() < 60.0);
+    }
+
+    #[test]
+    fn test_pruned_node_in_ibd() {
+        let node = MockNodeClient::new().with_blockchain_info(|info| {
+            info.initial_block_download = true;
+            info.pruned = true;
+            info.verification_progress = 0.42;
+            info.blocks = 350_000;
+            info.headers = 800_000;
+        });
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
 
         collector.collect();
 
+        assert_eq!(collector.metrics().initial_block_download.get(), 1.0);
+        assert_eq!(collector.metrics().chain_pruned.get(), 1.0);
+        assert_eq!(collector.metrics().node_synced.get(), 0.0);
+        assert_eq!(collector.metrics().headers_blocks_gap.get(), 450_000.0);
+        assert_eq!(collector.status().status, "syncing");
+    }
+
+    #[test]
+    fn test_ibd_skips_meaningless_sections() {
+        let node = MockNodeClient::new()
+            .with_blockchain_info(|info| info.initial_block_download = true)
+            .with_error("estimatesmartfee/2", "Insufficient data")
+            .with_error("getblockstats", "Block not available");
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+
+        let report = collector.collect();
+
+        assert_eq!(collector.metrics().node_in_ibd.get(), 1.0);
+        assert_eq!(collector.metrics().scrape_error.get(), 0.0);
+        assert!(!report.rpcs.contains_key("estimatesmartfee/2"));
+        assert!(!report.rpcs.contains_key("getblockstats"));
+        assert!(!report.rpcs.contains_key("getchaintxstats"));
+        assert!(report.rpcs.contains_key("getpeerinfo"));
+        let stale = collector.snapshot().section_stale;
+        assert!(!stale.contains_key("fee_estimates"));
+        assert!(stale.values().all(|&stale| stale == 0.0));
+    }
+
+    #[test]
+    fn test_node_in_ibd_clears_after_sync() {
+        let node = MockNodeClient::new().with_error("estimatesmartfee/2", "Insufficient data");
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+
+        let report = collector.collect();
+
+        assert_eq!(collector.metrics().node_in_ibd.get(), 0.0);
+        assert!(report.rpcs.contains_key("estimatesmartfee/2"));
+        assert_eq!(collector.metrics().scrape_error.get(), 1.0);
+    }
+
+    #[test]
+    fn test_section_stale() {
+        let config = CollectorConfig { disabled_sections: [Section::Mining].into(), ..Default::default() };
+        let node =
+            MockNodeClient::new().with_error("getpeerinfo", "timed out").with_error("estimatesmartfee/6", "timed out");
+        let collector = MetricsCollector::with_config(node, BitcoinMetrics::new().unwrap(), config);
+        collector.collect();
+
+        let stale = collector.snapshot().section_stale;
+        assert_eq!(stale["peers"], 1.0);
+        // One failed target leaves that target's estimate stale
+        assert_eq!(stale["fee_estimates"], 1.0);
+        assert_eq!(stale["blockchain"], 0.0);
+        assert_eq!(stale["block_stats"], 0.0);
+        assert!(!stale.contains_key("mining"));
+        assert!(!stale.contains_key("coinbase"));
+
+        // Without blockchain info, block stats aren't fetched and keep their old values too
+        let node = MockNodeClient::new().with_error("getblockchaininfo", "timed out");
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        let stale = collector.snapshot().section_stale;
+        assert_eq!(stale["blockchain"], 1.0);
+        assert_eq!(stale["block_stats"], 1.0);
+        assert_eq!(stale["peers"], 0.0);
+    }
+
+    #[test]
+    fn test_last_error_info_cleared_on_success() {
+        let node = MockNodeClient::new()
+            .with_error("getpeerinfo", "connection\n  reset")
+            .with_error("uptime", &"x".repeat(500));
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+        collector.collect();
+
+        let errors = collector.snapshot().last_errors;
+        assert_eq!(errors.len(), 2);
+        // The mock's errors display with a "Configuration error: " prefix
+        assert_eq!(errors["getpeerinfo"], "Configuration error: connection reset");
+        assert_eq!(errors["uptime"].chars().count(), ERROR_LABEL_MAX_CHARS + 3);
+        assert!(errors["uptime"].ends_with("xxx..."));
+        let labels = ["getpeerinfo", "Configuration error: connection reset"];
+        assert_eq!(collector.metrics().last_error_info.with_label_values(&labels).get(), 1.0);
+
+        // A new message replaces the method's series; a success removes it
+        let node = MockNodeClient::new().with_error("getpeerinfo", "timed out");
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        let errors = collector.snapshot().last_errors;
+        assert_eq!(errors, BTreeMap::from([("getpeerinfo".to_string(), "Configuration error: timed out".to_string())]));
+        assert_eq!(collector.metrics().last_error_info.collect()[0].get_metric().len(), 1);
+    }
+
+    #[test]
+    fn test_fee_pressure_index() {
+        let config = FeePressureConfig::default();
+        // An empty mempool at the relay fee with a cheap estimate: barely any pressure
+        assert_eq!(fee_pressure_index(&config, Some(0.0), 0.00001, 0.00001, Some(0.00001)), Some(0.0));
+        // Full mempool, minimum fee and estimate at or beyond the 100 sat/vB cap
+        assert_eq!(fee_pressure_index(&config, Some(1.2), 0.001, 0.00001, Some(0.005)), Some(1.0));
+        // A missing estimate is left out of the mean instead of pulling it down
+        assert_eq!(fee_pressure_index(&config, Some(1.0), 0.00001, 0.00001, None), Some(0.5));
+        let weights = FeePressureConfig { mempool_weight: 0.0, min_fee_weight: 0.0, ..config };
+        assert_eq!(fee_pressure_index(&weights, Some(1.0), 0.00001, 0.00001, None), None);
+        assert_eq!(fee_pressure_index(&weights, Some(1.0), 0.00001, 0.00001, Some(0.000505)), Some(0.5));
+    }
+
+    #[test]
+    fn test_fee_pressure_is_opt_in() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().fee_pressure_index.collect()[0].get_metric().len(), 0);
+
+        let config = CollectorConfig { fee_pressure: Some(FeePressureConfig::default()), ..Default::default() };
+        let collector = MetricsCollector::with_config(MockNodeClient::new(), BitcoinMetrics::new().unwrap(), config);
+        collector.collect();
+        // 10 MB of 300 MB used, minimum fee at the relay fee, 25 sat/vB for 2 blocks
+        let expected = (10.0 / 300.0 + 0.0 + 24.0 / 99.0) / 3.0;
+        let index = collector.metrics().fee_pressure_index.with_label_values::<&str>(&[]).get();
+        assert!((index - expected).abs() < 1e-9, "{index}");
+    }
+
+    #[test]
+    fn test_core_version_unsupported() {
+        let node = MockNodeClient::new().with_network_info(|info| info.version = 300000);
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+
+        collector.collect();
+
+        assert_eq!(collector.metrics().core_version_supported.get(), 0.0);
+        assert_eq!(crate::node::core_version_string(300000), "30.0.0");
+        assert_eq!(crate::node::core_version_string(280100), "28.1.0");
+    }
+
+    #[test]
+    fn test_network_proxy_configured() {
+        let node = MockNodeClient::new().with_network_info(|info| info.networks[1].proxy = "127.0.0.1:9050".into());
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+
+        collector.collect();
+
+        let proxy = &collector.metrics().network_proxy_configured;
+        assert_eq!(proxy.with_label_values(&["onion"]).get(), 1.0);
+        assert_eq!(proxy.with_label_values(&["ipv4"]).get(), 0.0);
+    }
+
+    #[test]
+    fn test_peers_on_limited_networks() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        let metrics = collector.metrics();
+        assert_eq!(metrics.network_limited.with_label_values(&["onion"]).get(), 1.0);
+        assert_eq!(metrics.network_limited.with_label_values(&["ipv4"]).get(), 0.0);
+        assert_eq!(metrics.peers_by_network.with_label_values(&["ipv4"]).get(), 2.0);
+        assert_eq!(metrics.peers_on_limited_networks.with_label_values::<&str>(&[]).get(), 0.0);
+
+        let node = MockNodeClient::new().with_peer_info(|peers| peers.0[1].network = "onion".into());
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        let metrics = collector.metrics();
+        assert_eq!(metrics.peers_by_network.with_label_values(&["onion"]).get(), 1.0);
+        assert_eq!(metrics.peers_on_limited_networks.with_label_values::<&str>(&[]).get(), 1.0);
+
+        // Without network info there's nothing to check the peers against
+        let node = MockNodeClient::new().with_error("getnetworkinfo", "unreachable");
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        assert_eq!(collector.metrics().peers_on_limited_networks.collect()[0].get_metric().len(), 0);
+    }
+
+    #[test]
+    fn test_sat_per_vb() {
+        assert_eq!(sat_per_vb(0.00001), 1.0);
+        // Core 29's default relay fee of 100 sat/kvB
+        assert_eq!(sat_per_vb(0.000001), 0.1);
+        assert_eq!(sat_per_vb(0.00025), 25.0);
+    }
+
+    #[test]
+    fn test_block_timestamp_lag() {
+        assert_eq!(block_timestamp_lag(1_700_000_600, 1_700_000_000), 600.0);
+        assert_eq!(block_timestamp_lag(1_700_000_000, 1_700_000_000), 0.0);
+        // Future-dated block (miner clock ahead or local clock behind) clamps to zero
+        assert_eq!(block_timestamp_lag(1_700_000_000, 1_700_003_600), 0.0);
+    }
+
+    #[test]
+    fn test_bytes_per_second() {
+        assert_eq!(bytes_per_second(1_000, 6_000, 2_500), Some(2_000.0));
+        assert_eq!(bytes_per_second(1_000, 1_000, 0), None);
+        // Totals reset when the node restarts
+        assert_eq!(bytes_per_second(6_000, 1_000, 2_500), None);
+    }
+
+    #[test]
+    fn test_disk_full_estimate() {
+        let start = Instant::now();
+        let mut samples = VecDeque::from([(start, 100.0)]);
+        assert_eq!(disk_full_estimate(&samples, 1000.0), None);
+
+        // 10 bytes/s with 800 bytes left
+        samples.push_back((start + Duration::from_secs(10), 200.0));
+        assert_eq!(disk_full_estimate(&samples, 1000.0), Some(80.0));
+
+        samples.push_back((start + Duration::from_secs(20), 100.0));
+        assert_eq!(disk_full_estimate(&samples, 1000.0), Some(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_partial_failure_sets_error_gauge() {
+        let metrics = BitcoinMetrics::new().unwrap();
+        let collector = MetricsCollector::new(MockNodeClient::new().with_error("getmempoolinfo", "simulated failure"), metrics);
+
+        let report = collector.collect();
+
         // Blockchain info should still be collected
         assert_eq!(collector.metrics().blocks.get(), 800_000.0);
         // But error gauge should be set
         assert_eq!(collector.metrics().scrape_error.get(), 1.0);
+
+        // And the report pinpoints the failing RPC
+        assert!(report.rpcs["getblockchaininfo"].ok);
+        assert!(!report.rpcs["getmempoolinfo"].ok);
+        assert!(report.rpcs["getmempoolinfo"].error.as_deref().unwrap().contains("simulated failure"));
+        assert!(report.rpcs["estimatesmartfee/144"].ok);
+    }
+
+    #[test]
+    fn test_extra_metrics() {
+        let extra = |name: &str, method: &str, pointer: &str| ExtraMetricConfig {
+            name: name.to_string(),
+            help: "test".to_string(),
+            rpc_method: method.to_string(),
+            params: Vec::new(),
+            json_pointer: pointer.to_string(),
+        };
+        let metrics = BitcoinMetrics::builder()
+            .extra_metrics(&[
+                extra("taproot_height", "getdeploymentinfo", "/deployments/taproot/height"),
+                extra("taproot_active", "getdeploymentinfo", "/deployments/taproot/active"),
+                extra("taproot_type", "getdeploymentinfo", "/deployments/taproot/type"),
+                extra("rpc_active_commands", "getrpcinfo", "/active_commands/0/duration"),
+            ])
+            .build()
+            .unwrap();
+        let node = MockNodeClient::new()
+            .with_json_response(
+                "getdeploymentinfo",
+                serde_json::json!({"deployments": {"taproot": {"type": "buried", "active": true, "height": 709_632}}}),
+            )
+            .with_error("getrpcinfo", "simulated failure");
+        let collector = MetricsCollector::new(node, metrics);
+
+        let report = collector.collect();
+
+        let extra = &collector.metrics().extra;
+        assert_eq!(extra[0].gauge.get(), 709_632.0);
+        assert_eq!(extra[1].gauge.get(), 1.0);
+        // A string and a failed RPC leave the gauge unset and count an error
+        let errors = &collector.metrics().extra_metric_errors;
+        assert_eq!(errors.with_label_values(&["taproot_type"]).get(), 1);
+        assert_eq!(errors.with_label_values(&["rpc_active_commands"]).get(), 1);
+        assert_eq!(errors.with_label_values(&["taproot_height"]).get(), 0);
+        // The shared RPC is called once
+        assert!(report.rpcs["getdeploymentinfo"].ok);
+        assert!(!report.rpcs["getrpcinfo"].ok);
+    }
+
+    #[test]
+    fn test_accepting_inbound_needs_local_address_and_network() {
+        let node = MockNodeClient::new().with_network_info(|info| info.local_addresses.clear());
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().accepting_inbound.get(), 0.0);
+        assert_eq!(collector.metrics().local_addresses_count.get(), 0.0);
+
+        let node = MockNodeClient::new().with_network_info(|info| info.network_active = false);
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().accepting_inbound.get(), 0.0);
+        assert_eq!(collector.metrics().local_addresses_count.get(), 1.0);
+    }
+
+    #[test]
+    fn test_scrapes_in_progress() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        let gauge = &collector.metrics().scrapes_in_progress;
+        assert_eq!(gauge.get(), 0.0);
+
+        let first = InProgress::start(gauge);
+        let second = InProgress::start(gauge);
+        assert_eq!(gauge.get(), 2.0);
+        drop(first);
+        drop(second);
+        assert_eq!(gauge.get(), 0.0);
+
+        // A panicking collection still gives its slot back
+        let result = std::panic::catch_unwind(|| {
+            let _in_progress = InProgress::start(gauge);
+            panic!("simulated panic");
+        });
+        assert!(result.is_err());
+        assert_eq!(gauge.get(), 0.0);
+    }
+
+    #[test]
+    fn test_fresh_regtest_node_is_not_an_error() {
+        let config = CollectorConfig { coinbase_detail: true, mempool_detail: true, ..Default::default() };
+        let collector = MetricsCollector::with_config(MockNodeClient::regtest(), BitcoinMetrics::new().unwrap(), config);
+
+        let report = collector.collect();
+
+        assert!(!report.had_error(), "{:?}", report.rpcs);
+        assert_eq!(collector.metrics().scrape_error.get(), 0.0);
+        assert_eq!(collector.metrics().blocks.get(), 0.0);
+        assert!((collector.metrics().difficulty_log2.get() + 31.0).abs() < 1e-4);
+        assert_eq!(collector.metrics().peer_count.get(), 0.0);
+        // Genesis has no block stats or retrievable coinbase, and no fee data yet
+        assert!(!report.rpcs.contains_key("getblockstats"));
+        assert!(!report.rpcs.contains_key("getrawtransaction"));
+        assert_eq!(collector.metrics().latest_block_txs.get(), 0.0);
+        assert_eq!(collector.metrics().fee_estimate_available.with_label_values(&["2"]).get(), 0.0);
+        assert_eq!(collector.metrics().fee_estimate_2_blocks.get(), 0.0);
+        assert!(!report.rpcs.contains_key("getblockheader"));
+        assert!(collector.snapshot().section_stale.values().all(|&stale| stale == 0.0));
+        assert_eq!(collector.metrics().last_block_interval_seconds.collect()[0].get_metric().len(), 0);
+    }
+
+    #[test]
+    fn test_last_block_interval_reuses_known_headers() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().last_block_interval_seconds.with_label_values::<&str>(&[]).get(), 600.0);
+
+        // An unchanged tip needs no headers
+        let collector =
+            MetricsCollector { node: MockNodeClient::new().with_error("getblockheader", "unreachable"), ..collector };
+        let report = collector.collect();
+        assert!(!report.rpcs.contains_key("getblockheader"));
+        assert_eq!(collector.metrics().last_block_interval_seconds.with_label_values::<&str>(&[]).get(), 600.0);
+
+        // A tip built on the previous one takes its parent's time from the cache, not the node
+        let (tip, next) = (format!("{:064x}", 0), format!("{:064x}", 800_001));
+        let node = MockNodeClient::new()
+            .with_blockchain_info(|info| {
+                info.blocks = 800_001;
+                info.best_block_hash = next.clone();
+                info.time = 1_700_000_010;
+            })
+            .with_block_header(&next, BlockHeader { time: 1_700_000_010, previous_block_hash: Some(tip.clone()) })
+            .with_block_header(&tip, BlockHeader { time: 0, previous_block_hash: None });
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        assert_eq!(collector.metrics().last_block_interval_seconds.with_label_values::<&str>(&[]).get(), 10.0);
+    }
+
+    #[test]
+    fn test_best_block_info() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().best_block_info.with_label_values(&[format!("{:064x}", 0)]).get(), 1.0);
+
+        // A reorg to another block at the same height replaces the series
+        let hash = format!("{:064x}", 1);
+        let node = MockNodeClient::new().with_blockchain_info(|info| info.best_block_hash = hash.clone());
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        assert_eq!(collector.metrics().best_block_info.collect()[0].get_metric().len(), 1);
+        assert_eq!(collector.metrics().best_block_info.with_label_values(&[hash]).get(), 1.0);
+    }
+
+    #[test]
+    fn test_reorgs() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        collector.collect();
+        assert_eq!(collector.metrics().reorgs.get(), 0);
+
+        // Two blocks on top of the previous tip
+        let (tip, next, next2) = (format!("{:064x}", 0), format!("{:064x}", 800_001), format!("{:064x}", 800_002));
+        let node = MockNodeClient::new()
+            .with_blockchain_info(|info| {
+                info.blocks = 800_002;
+                info.best_block_hash = next2.clone();
+            })
+            .with_block_header(&next2, BlockHeader { time: 1_700_001_200, previous_block_hash: Some(next.clone()) })
+            .with_block_header(&next, BlockHeader { time: 1_700_000_600, previous_block_hash: Some(tip.clone()) });
+        let collector = MetricsCollector { node, ..collector };
+        let report = collector.collect();
+        assert!(report.rpcs.contains_key("getblockhash"));
+        assert_eq!(collector.metrics().reorgs.get(), 0);
+
+        // A competing block at the same height
+        let stale = format!("{:064x}", 1);
+        let node = MockNodeClient::new().with_blockchain_info(|info| {
+            info.blocks = 800_002;
+            info.best_block_hash = stale.clone();
+        });
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        assert_eq!(collector.metrics().reorgs.get(), 1);
+
+        // A higher tip on a branch that doesn't include the previous one
+        let node = MockNodeClient::new()
+            .with_blockchain_info(|info| {
+                info.blocks = 800_003;
+                info.best_block_hash = format!("{:064x}", 800_003);
+            })
+            .with_block_header(&format!("{:064x}", 800_003), BlockHeader { time: 0, previous_block_hash: Some(next2) });
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        assert_eq!(collector.metrics().reorgs.get(), 2);
+    }
+
+    #[test]
+    fn test_block_stats_fetched_once_per_tip() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        assert!(collector.collect().rpcs.contains_key("getblockstats"));
+
+        // Same tip: the cached stats are exported without asking the node
+        let node = MockNodeClient::new().with_error("getblockstats", "unreachable");
+        let collector = MetricsCollector { node, ..collector };
+        let report = collector.collect();
+        assert!(!report.rpcs.contains_key("getblockstats"));
+        assert_eq!(collector.metrics().latest_block_txs.get(), 2500.0);
+        assert_eq!(collector.snapshot().section_stale["block_stats"], 0.0);
+
+        // A new tip fetches its own stats
+        let node = MockNodeClient::new()
+            .with_blockchain_info(|info| info.best_block_hash = format!("{:064x}", 800_001))
+            .with_error("getblockstats", "unreachable");
+        let collector = MetricsCollector { node, ..collector };
+        let report = collector.collect();
+        assert!(!report.rpcs["getblockstats"].ok);
+        assert_eq!(collector.snapshot().section_stale["block_stats"], 1.0);
+    }
+
+    #[test]
+    fn test_scan_descriptors() {
+        let watched =
+            |name: &str, descriptor: &str| WatchedDescriptorConfig { name: name.into(), descriptor: descriptor.into() };
+        let node = MockNodeClient::new().with_descriptor_balance("addr(bc1qcold)", 12.5);
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap())
+            .with_watched_descriptors(vec![watched("cold", "addr(bc1qcold)"), watched("typo", "adr(bc1q)")]);
+
+        // Scans only run on their own interval, not per collection
+        let report = collector.collect();
+        assert!(!report.rpcs.keys().any(|method| method.starts_with("scantxoutset")));
+
+        let report = collector.scan_descriptors();
+        let metrics = collector.metrics();
+        assert_eq!(metrics.watched_descriptor_balance.with_label_values(&["cold"]).get(), 12.5);
+        assert_eq!(metrics.watched_descriptor_balance.collect()[0].get_metric().len(), 1);
+        assert_eq!(metrics.watched_descriptor_scan_errors.with_label_values(&["typo"]).get(), 1);
+        assert!(report.rpcs["scantxoutset/cold"].ok);
+        assert!(!report.rpcs["scantxoutset/typo"].ok);
+    }
+
+    #[test]
+    fn test_wallets_labeled_and_unloaded_skipped() {
+        let info = WalletInfo { balance: Some(1.5), unconfirmed_balance: Some(0.25), immature_balance: None, tx_count: 42 };
+        let node = MockNodeClient::new().with_wallet("hot", info);
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap())
+            .with_wallets(vec!["hot".to_string(), "cold".to_string()]);
+
+        let report = collector.collect();
+
+        let metrics = collector.metrics();
+        assert_eq!(metrics.wallet_balance.with_label_values(&["hot"]).get(), 1.5);
+        assert_eq!(metrics.wallet_unconfirmed_balance.with_label_values(&["hot"]).get(), 0.25);
+        assert_eq!(metrics.wallet_tx_count.with_label_values(&["hot"]).get(), 42.0);
+        assert_eq!(metrics.wallet_immature_balance.collect()[0].get_metric().len(), 0);
+        // The unloaded wallet exports nothing and is counted
+        assert_eq!(metrics.wallet_balance.collect()[0].get_metric().len(), 1);
+        assert_eq!(metrics.wallet_errors.with_label_values(&["cold"]).get(), 1);
+        assert_eq!(metrics.wallet_errors.with_label_values(&["hot"]).get(), 0);
+        assert!(report.rpcs["getwalletinfo/hot"].ok);
+        assert!(!report.rpcs["getwalletinfo/cold"].ok);
+    }
+
+    #[test]
+    fn test_mempool_min_fee_above_relay() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().mempool_min_fee_above_relay.get(), 0.0);
+        assert_eq!(collector.metrics().mempool_min_fee_ratio.get(), 1.0);
+
+        let node = MockNodeClient::new().with_mempool_info(|info| info.mempool_min_fee = 0.00004);
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().mempool_min_fee_above_relay.get(), 1.0);
+        assert!((collector.metrics().mempool_min_fee_ratio.get() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mempool_eviction_imminent() {
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().mempool_headroom_bytes.get(), 290_000_000.0);
+        assert_eq!(collector.metrics().mempool_eviction_imminent.get(), 0.0);
+
+        // 10 MB of a 300 MB mempool left is below the default 5%
+        let node = MockNodeClient::new().with_mempool_info(|info| info.usage = 290_000_000);
+        let collector = MetricsCollector { node, ..collector };
+        collector.collect();
+        assert_eq!(collector.metrics().mempool_headroom_bytes.get(), 10_000_000.0);
+        assert_eq!(collector.metrics().mempool_eviction_imminent.get(), 1.0);
+
+        let config = CollectorConfig { mempool_eviction_headroom_percent: 2.0, ..Default::default() };
+        let node = MockNodeClient::new().with_mempool_info(|info| info.usage = 290_000_000);
+        let collector = MetricsCollector::with_config(node, BitcoinMetrics::new().unwrap(), config);
+        collector.collect();
+        assert_eq!(collector.metrics().mempool_eviction_imminent.get(), 0.0);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let metrics = BitcoinMetrics::builder().node(Some("a")).instance(Some("10.0.0.2:8332")).build().unwrap();
+        let collector = MetricsCollector::new(MockNodeClient::new(), metrics).with_expected_chain(Some("main".into()));
+        assert_eq!(collector.snapshot().blocks, 0.0);
+
+        collector.collect();
+        let snapshot = collector.snapshot();
+
+        assert_eq!(snapshot.blocks, 800_000.0);
+        assert_eq!(snapshot.connections_in, 85.0);
+        assert_eq!(snapshot.fee_estimate_6_blocks, 0.00015);
+        assert_eq!(snapshot.chain_mismatch, Some(0.0));
+        assert_eq!(snapshot.connections_saturation_ratio, None);
+        // Labeled gauges are keyed by their label value, without the node and instance labels
+        assert_eq!(snapshot.network_reachable, BTreeMap::from([("ipv4".into(), 1.0), ("onion".into(), 0.0)]));
+        assert_eq!(snapshot.peers_by_transport.values().sum::<f64>(), 2.0);
+        assert_eq!(snapshot.fee_estimate_available["144"], 1.0);
+    }
+
+    #[test]
+    fn test_outbound_block_relay_connections() {
+        let node = MockNodeClient::new().with_peer_info(|peers| {
+            let mut block_relay = peers.0[0].clone();
+            block_relay.connection_type = Some("block-relay-only".into());
+            peers.0.push(block_relay.clone());
+            peers.0.push(block_relay);
+        });
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+
+        collector.collect();
+
+        assert_eq!(collector.metrics().outbound_block_relay_connections.get(), 2.0);
+        assert_eq!(collector.metrics().peers_outbound.get(), 3.0);
+    }
+
+    #[test]
+    fn test_peers_oldest_last_block() {
+        // Neither mock peer has relayed a block
+        let collector = MetricsCollector::new(MockNodeClient::new(), BitcoinMetrics::new().unwrap());
+        collector.collect();
+        assert_eq!(collector.metrics().peers_oldest_last_block_seconds.collect()[0].get_metric().len(), 0);
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let node = MockNodeClient::new().with_peer_info(|peers| {
+            peers.0[0].last_block = now - 1800;
+            peers.0[1].last_block = now - 600;
+        });
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+        collector.collect();
+        let age = collector.metrics().peers_oldest_last_block_seconds.with_label_values::<&str>(&[]).get();
+        assert!((600.0..605.0).contains(&age), "{age}");
+    }
+
+    #[test]
+    fn test_peers_connection_age() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let node = MockNodeClient::new().with_peer_info(|peers| {
+            peers.0[0].connection_time = now - 3600;
+            peers.0[1].connection_time = now - 600;
+            let mut unset = peers.0[1].clone();
+            unset.connection_time = 0;
+            let mut future = peers.0[1].clone();
+            future.connection_time = now + 86_400;
+            peers.0.extend([unset, future]);
+        });
+        let collector = MetricsCollector::new(node, BitcoinMetrics::new().unwrap());
+        collector.collect();
+        let avg = collector.metrics().peers_avg_connection_age_seconds.get();
+        let min = collector.metrics().peers_min_connection_age_seconds.get();
+        assert!((2100.0..2105.0).contains(&avg), "{avg}");
+        assert!((600.0..605.0).contains(&min), "{min}");
+    }
+
+    #[test]
+    fn test_peer_detail_caps_series() {
+        let config = CollectorConfig { peer_detail: true, ..Default::default() };
+        let collector = MetricsCollector::with_config(MockNodeClient::new(), BitcoinMetrics::new().unwrap(), config);
+        collector.collect();
+        let metrics = collector.metrics();
+        assert_eq!(metrics.peer_bytes_sent.with_label_values(&["1.2.3.4:8333"]).get(), 50_000.0);
+        assert_eq!(metrics.peer_bytes_received.with_label_values(&["5.6.7.8:8333"]).get(), 60_000.0);
+        assert_eq!(metrics.peer_series_truncated.get(), 0.0);
+
+        // With a cap of one, the busier peer keeps its series and the other goes to "others"
+        let config = CollectorConfig { peer_detail: true, max_peer_series: 1, ..Default::default() };
+        let collector = MetricsCollector::with_config(MockNodeClient::new(), BitcoinMetrics::new().unwrap(), config);
+        collector.collect();
+        let metrics = collector.metrics();
+        assert_eq!(metrics.peer_bytes_sent.collect()[0].get_metric().len(), 2);
+        assert_eq!(metrics.peer_bytes_sent.with_label_values(&["1.2.3.4:8333"]).get(), 50_000.0);
+        assert_eq!(metrics.peer_bytes_sent.with_label_values(&["others"]).get(), 30_000.0);
+        assert_eq!(metrics.peer_bytes_received.with_label_values(&["others"]).get(), 60_000.0);
+        assert_eq!(metrics.peer_series_truncated.get(), 1.0);
     }
 }
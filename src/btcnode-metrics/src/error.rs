@@ -1,5 +1,10 @@
 use thiserror::Error;
 
+use crate::transport::TransportError;
+
+/// JSON-RPC code bitcoind returns for an RPC it doesn't know.
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Bitcoin RPC error: {0}")]
@@ -11,3 +16,50 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Config(String),
 }
+
+/// Broad cause of an [`Error`], for telling an operator what to fix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The node rejected the RPC credentials.
+    Auth,
+    /// The node couldn't be reached, or what answered isn't an RPC server.
+    Connection,
+    /// The node answered in a shape this exporter doesn't understand (unsupported Core version).
+    VersionMismatch,
+    /// The node reported an error for the call itself.
+    Rpc,
+    Config,
+    Other,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        use corepc_client::client_sync::Error as RpcError;
+
+        let Error::Rpc(rpc) = self else {
+            return if matches!(self, Error::Config(_)) { ErrorKind::Config } else { ErrorKind::Other };
+        };
+        match rpc {
+            RpcError::JsonRpc(jsonrpc::Error::Transport(e)) => match e.downcast_ref::<TransportError>() {
+                Some(TransportError::Status { status: 401 | 403, .. }) => ErrorKind::Auth,
+                // A non-HTTP or non-JSON answer usually means the port is wrong (e.g. the P2P port)
+                Some(
+                    TransportError::Io(_)
+                    | TransportError::Url(_)
+                    | TransportError::Malformed(_)
+                    | TransportError::Json(_),
+                ) => ErrorKind::Connection,
+                _ => ErrorKind::Other,
+            },
+            RpcError::JsonRpc(jsonrpc::Error::Rpc(e)) if e.code == RPC_METHOD_NOT_FOUND => ErrorKind::VersionMismatch,
+            RpcError::JsonRpc(jsonrpc::Error::Rpc(_)) | RpcError::Returned(_) => ErrorKind::Rpc,
+            RpcError::JsonRpc(jsonrpc::Error::Json(_))
+            | RpcError::Json(_)
+            | RpcError::UnexpectedStructure
+            | RpcError::ServerVersion(_) => ErrorKind::VersionMismatch,
+            RpcError::Io(_) => ErrorKind::Connection,
+            RpcError::MissingUserPassword | RpcError::InvalidCookieFile => ErrorKind::Auth,
+            _ => ErrorKind::Other,
+        }
+    }
+}
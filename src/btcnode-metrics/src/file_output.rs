@@ -0,0 +1,103 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use prometheus::{IntCounter, Registry};
+
+use crate::Error;
+use crate::config::FileOutputConfig;
+
+/// Writes the exposition text to a file, for nodes no scraper can reach whose metrics are
+/// shipped out-of-band. Each write replaces the file atomically, so a reader never sees a
+/// partial one.
+pub struct FileWriter {
+    path: PathBuf,
+    keep: usize,
+    failures: IntCounter,
+}
+
+impl FileWriter {
+    pub fn new(config: &FileOutputConfig) -> Result<Self, Error> {
+        if config.path.file_name().is_none() {
+            return Err(Error::Config(format!("file_output: {} is not a file path", config.path.display())));
+        }
+        let failures = IntCounter::new(
+            "bitcoin_exporter_file_output_failures_total",
+            "Number of writes of the metrics file that failed",
+        )?;
+
+        Ok(Self { path: config.path.clone(), keep: config.keep, failures })
+    }
+
+    /// Registers the write failure counter.
+    pub fn register_metrics(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.failures.clone()))
+    }
+
+    /// Replaces the file with `body`, first shifting the previous `keep` files to `<path>.1`,
+    /// `<path>.2` and so on.
+    pub fn write(&self, body: &str) -> io::Result<()> {
+        let result = self.write_file(body);
+        if result.is_err() {
+            self.failures.inc();
+        }
+        result
+    }
+
+    fn write_file(&self, body: &str) -> io::Result<()> {
+        // In the same directory, so the rename below can't cross filesystems
+        let tmp = sibling(&self.path, ".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(body.as_bytes())?;
+        file.sync_all()?;
+
+        for n in (1..self.keep).rev() {
+            rename_existing(&sibling(&self.path, &format!(".{n}")), &sibling(&self.path, &format!(".{}", n + 1)))?;
+        }
+        if self.keep > 0 {
+            rename_existing(&self.path, &sibling(&self.path, ".1"))?;
+        }
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// `path` with `suffix` appended to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn rename_existing(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_rotates() {
+        let dir = std::env::temp_dir().join(format!("btcnode-metrics-file-output-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("metrics.prom");
+        let writer = FileWriter::new(&FileOutputConfig { path: path.clone(), interval_seconds: 15, keep: 2 }).unwrap();
+
+        for body in ["a", "b", "c", "d"] {
+            writer.write(body).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "d");
+        assert_eq!(fs::read_to_string(sibling(&path, ".1")).unwrap(), "c");
+        assert_eq!(fs::read_to_string(sibling(&path, ".2")).unwrap(), "b");
+        assert!(!sibling(&path, ".3").exists() && !sibling(&path, ".tmp").exists());
+
+        let overwriting = FileWriter::new(&FileOutputConfig { path: path.clone(), interval_seconds: 15, keep: 0 });
+        overwriting.unwrap().write("e").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "e");
+        assert_eq!(fs::read_to_string(sibling(&path, ".1")).unwrap(), "c");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
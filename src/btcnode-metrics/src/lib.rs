@@ -1,13 +1,60 @@
+//! Collects Bitcoin Core node metrics over RPC into a Prometheus registry.
+//!
+//! This crate has no HTTP server and no async runtime; the `btcnode-prom-metrics` binary
+//! adds those. To embed it in a service of your own, the surface you need is:
+//!
+//! - [`BitcoinMetrics`]: the gauges, on their own [`prometheus::Registry`] or on yours
+//!   (see [`BitcoinMetricsBuilder::registry`]).
+//! - [`MetricsCollector`]: refreshes the gauges from a [`BitcoinNode`] on each
+//!   [`MetricsCollector::collect`], or hands you a [`BitcoinMetricsSnapshot`].
+//! - [`MetricsService`]: optionally, collects and encodes in one go for a `/metrics` handler,
+//!   with scrape timeouts and several nodes handled.
+//!
+//! Collection makes blocking RPC calls, so call it from a blocking task in async code.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use btcnode_metrics::{AppConfig, BitcoinMetrics, BitcoinNode, MetricsCollector, MetricsService};
+//!
+//! # fn main() -> Result<(), btcnode_metrics::Error> {
+//! let config = AppConfig::load(Path::new("config.toml"))?;
+//! let node = BitcoinNode::new(&config.nodes[0])?;
+//! let collector = MetricsCollector::with_config(node, BitcoinMetrics::new()?, config.collector);
+//! let service = MetricsService::new(collector);
+//! // Text exposition format, for the body of a `/metrics` response
+//! let body = service.scrape();
+//! # Ok(())
+//! # }
+//! ```
+
 pub mod collector;
 pub mod config;
 pub mod error;
+pub mod file_output;
 pub mod metrics;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
 pub mod node;
+pub mod openmetrics;
+pub mod remote_write;
+pub mod report;
 pub mod service;
+pub mod snapshot;
+pub mod ssh_tunnel;
+pub mod transport;
 
-pub use config::AppConfig;
-pub use error::Error;
-pub use metrics::BitcoinMetrics;
+pub use config::{
+    AppConfig, CollectorConfig, ExtraMetricConfig, FeePressureConfig, FileOutputConfig, RemoteWriteConfig, Section,
+    SshTunnelConfig, WatchedDescriptorConfig,
+};
+pub use error::{Error, ErrorKind};
+pub use file_output::FileWriter;
+pub use metrics::{BitcoinMetrics, BitcoinMetricsBuilder};
 pub use node::{BitcoinNode, NodeClient};
 pub use collector::MetricsCollector;
+pub use remote_write::RemoteWriter;
+pub use report::{CollectRecord, CollectReport};
 pub use service::MetricsService;
+pub use snapshot::BitcoinMetricsSnapshot;
+pub use ssh_tunnel::SshTunnel;
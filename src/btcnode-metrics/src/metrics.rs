@@ -1,18 +1,41 @@
-use prometheus::{Gauge, Registry, Opts};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{Gauge, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec, Registry, Opts};
 
 use crate::Error;
+use crate::config::{ExtraMetricConfig, Section};
 
+/// Every gauge a node exports; new gauges also need an entry in [`crate::snapshot`].
 pub struct BitcoinMetrics {
     pub registry: Registry,
+    /// Value of the `node` const label on every metric, when several nodes share the registry.
+    pub node: Option<String>,
 
     // Blockchain info
     pub blocks: Gauge,
     pub headers: Gauge,
+    pub headers_blocks_gap: Gauge,
     pub difficulty: Gauge,
+    pub difficulty_log2: Gauge,
     pub verification_progress: Gauge,
     pub size_on_disk: Gauge,
+    pub disk_full_estimate_seconds: GaugeVec,
     pub initial_block_download: Gauge,
+    pub node_synced: Gauge,
+    pub node_in_ibd: Gauge,
+    pub seconds_since_synced: Gauge,
     pub chain_pruned: Gauge,
+    pub chain_work_log2: Gauge,
+    pub chain_mismatch: GaugeVec,
+    pub best_block_timestamp: Gauge,
+    pub best_block_info: GaugeVec,
+    pub reorgs: IntCounter,
+    pub block_timestamp_lag_seconds: Gauge,
+    pub last_block_interval_seconds: GaugeVec,
 
     // Mempool info
     pub mempool_transactions: Gauge,
@@ -21,29 +44,70 @@ pub struct BitcoinMetrics {
     pub mempool_max_bytes: Gauge,
     pub mempool_min_fee: Gauge,
     pub mempool_total_fee: Gauge,
+    pub mempool_total_fee_sat: Gauge,
     pub mempool_min_relay_tx_fee: Gauge,
+    pub mempool_min_fee_above_relay: Gauge,
+    pub mempool_min_fee_ratio: Gauge,
+    pub mempool_headroom_bytes: Gauge,
+    pub mempool_eviction_imminent: Gauge,
     pub mempool_incremental_relay_fee: Gauge,
     pub mempool_unbroadcast_count: Gauge,
     pub mempool_full_rbf: Gauge,
+    pub fee_pressure_index: GaugeVec,
+
+    // Mempool detail (opt-in, from the verbose mempool)
+    pub mempool_tx_vsize_p50: Gauge,
+    pub mempool_tx_vsize_p90: Gauge,
+    pub mempool_tx_vsize_p99: Gauge,
+    pub mempool_oldest_unbroadcast_seconds: Gauge,
 
     // Network info
     pub connections: Gauge,
     pub connections_in: Gauge,
     pub connections_out: Gauge,
+    pub connections_saturation_ratio: GaugeVec,
     pub network_active: Gauge,
+    pub accepting_inbound: Gauge,
+    pub local_addresses_count: Gauge,
     pub node_version: Gauge,
     pub protocol_version: Gauge,
+    pub core_version_supported: Gauge,
     pub time_offset: Gauge,
     pub relay_fee: Gauge,
     pub incremental_fee: Gauge,
+    pub relay_fee_sat_per_vb: Gauge,
+    pub incremental_fee_sat_per_vb: Gauge,
+    pub network_reachable: GaugeVec,
+    pub network_proxy_configured: GaugeVec,
+    pub network_limited: GaugeVec,
 
     // Peer info (aggregated)
     pub peer_count: Gauge,
     pub peers_inbound: Gauge,
     pub peers_outbound: Gauge,
+    pub outbound_block_relay_connections: Gauge,
     pub peers_total_bytes_sent: Gauge,
     pub peers_total_bytes_received: Gauge,
     pub peers_avg_ping_seconds: Gauge,
+    pub peers_avg_min_ping_seconds: Gauge,
+    pub peers_worst_min_ping_seconds: Gauge,
+    pub peers_avg_connection_age_seconds: Gauge,
+    pub peers_min_connection_age_seconds: Gauge,
+    pub peers_min_fee_filter: Gauge,
+    pub peers_max_fee_filter: Gauge,
+    pub peers_max_synced_headers: Gauge,
+    pub peers_addresses_processed: Gauge,
+    pub peers_addresses_rate_limited: Gauge,
+    pub peers_by_transport: GaugeVec,
+    pub blocks_behind_peers: Gauge,
+    pub peers_oldest_last_block_seconds: GaugeVec,
+    pub peers_by_network: GaugeVec,
+    pub peers_on_limited_networks: GaugeVec,
+
+    // Peer detail (opt-in, labeled by peer address)
+    pub peer_bytes_sent: GaugeVec,
+    pub peer_bytes_received: GaugeVec,
+    pub peer_series_truncated: Gauge,
 
     // Mining info
     pub network_hash_ps: Gauge,
@@ -55,24 +119,32 @@ pub struct BitcoinMetrics {
     pub chain_tx_window_block_count: Gauge,
     pub chain_tx_window_tx_count: Gauge,
     pub chain_tx_window_interval: Gauge,
+    pub chain_tx_per_day: Gauge,
 
     // Net totals
     pub net_total_bytes_received: Gauge,
     pub net_total_bytes_sent: Gauge,
+    pub net_bytes_received_per_second: GaugeVec,
+    pub net_bytes_sent_per_second: GaugeVec,
 
     // Fee estimation (BTC/kvB for various confirmation targets)
     pub fee_estimate_2_blocks: Gauge,
     pub fee_estimate_6_blocks: Gauge,
     pub fee_estimate_12_blocks: Gauge,
     pub fee_estimate_144_blocks: Gauge,
+    pub fee_estimate_available: GaugeVec,
+    pub fee_estimate_fallback: GaugeVec,
+    pub fee_estimate_last_update: GaugeVec,
 
     // Chain tips
     pub chain_tips_count: Gauge,
 
     // Uptime
     pub node_uptime_seconds: Gauge,
+    pub node_start_timestamp: Gauge,
 
     // Latest block stats
+    pub latest_block_height: Gauge,
     pub latest_block_txs: Gauge,
     pub latest_block_size: Gauge,
     pub latest_block_weight: Gauge,
@@ -92,97 +164,387 @@ pub struct BitcoinMetrics {
     pub latest_block_segwit_total_weight: Gauge,
     pub latest_block_total_out: Gauge,
     pub latest_block_utxo_increase: Gauge,
+    pub latest_block_utxo_size_increase: Gauge,
+    pub latest_block_utxo_increase_actual: Gauge,
+    pub latest_block_utxo_size_increase_actual: Gauge,
+    pub latest_block_avg_tx_size: Gauge,
+    pub latest_block_median_tx_size: Gauge,
+    pub latest_block_max_tx_size: Gauge,
+    pub latest_block_min_tx_size: Gauge,
     pub latest_block_fee_rate_10th: Gauge,
     pub latest_block_fee_rate_25th: Gauge,
     pub latest_block_fee_rate_50th: Gauge,
     pub latest_block_fee_rate_75th: Gauge,
     pub latest_block_fee_rate_90th: Gauge,
 
+    // Latest block coinbase (opt-in)
+    pub latest_block_coinbase_value: Gauge,
+    pub latest_block_pool: GaugeVec,
+
+    // Chainstates (assumeutxo)
+    pub chainstate_blocks: GaugeVec,
+    pub chainstate_validated: GaugeVec,
+
+    // Node warnings
+    pub warnings_active: Gauge,
+    pub warning_info: GaugeVec,
+
+    // Wallets (labeled by wallet)
+    pub wallet_balance: GaugeVec,
+    pub wallet_unconfirmed_balance: GaugeVec,
+    pub wallet_immature_balance: GaugeVec,
+    pub wallet_tx_count: GaugeVec,
+    pub wallet_errors: IntCounterVec,
+
+    // Watched descriptors (labeled by descriptor name)
+    pub watched_descriptor_balance: GaugeVec,
+    pub watched_descriptor_scan_errors: IntCounterVec,
+
     // Collector meta
     pub scrape_duration_seconds: Gauge,
+    pub scrape_duration_histogram: Histogram,
     pub scrape_error: Gauge,
+    pub node_rpc_auth_ok: GaugeVec,
+    pub scrape_timed_out: Gauge,
+    pub seconds_since_last_scrape: Gauge,
+    pub scrapes_in_progress: Gauge,
+    pub last_error_info: GaugeVec,
+    pub section_stale: GaugeVec,
+
+    // Extra metrics (`[[extra_metric]]`)
+    pub extra: Vec<ExtraMetric>,
+    pub extra_metric_errors: IntCounterVec,
+
+    /// Every section's metrics, to register or unregister them when sections change at runtime.
+    section_metrics: BTreeMap<Section, Vec<Arc<dyn Collector>>>,
+    registered_sections: Mutex<BTreeSet<Section>>,
+}
+
+/// A configured `[[extra_metric]]` and the gauge it sets.
+pub struct ExtraMetric {
+    pub config: ExtraMetricConfig,
+    pub gauge: Gauge,
+}
+
+/// Registers metrics on a shared registry, tagging each with the node's const labels.
+/// Metrics of disabled sections are still created, just not registered until
+/// [`BitcoinMetrics::set_sections`] enables them.
+struct Registrar<'a> {
+    registry: &'a Registry,
+    const_labels: HashMap<String, String>,
+    sections: &'a BTreeSet<Section>,
+    current: Cell<Option<Section>>,
+    section_metrics: RefCell<BTreeMap<Section, Vec<Arc<dyn Collector>>>>,
+}
+
+impl Registrar<'_> {
+    fn opts(&self, name: &str, help: &str) -> Opts {
+        Opts::new(name, help).const_labels(self.const_labels.clone())
+    }
+
+    /// Starts registering the metrics of `section`.
+    fn section(&self, section: Section) {
+        self.current.set(Some(section));
+    }
+
+    /// Starts registering metrics that belong to no section.
+    fn always(&self) {
+        self.current.set(None);
+    }
+
+    fn register(&self, collector: Box<dyn Collector>) -> Result<(), prometheus::Error> {
+        let Some(section) = self.current.get() else {
+            return self.registry.register(collector);
+        };
+        let collector: Arc<dyn Collector> = Arc::from(collector);
+        self.section_metrics.borrow_mut().entry(section).or_default().push(collector.clone());
+        if self.sections.contains(&section) { self.registry.register(Box::new(Shared(collector))) } else { Ok(()) }
+    }
+}
+
+/// A metric kept by [`BitcoinMetrics`] while also registered, so it can be unregistered later.
+struct Shared(Arc<dyn Collector>);
+
+impl Collector for Shared {
+    fn desc(&self) -> Vec<&Desc> {
+        self.0.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.0.collect()
+    }
 }
 
 macro_rules! register_gauge {
     ($registry:expr, $name:expr, $help:expr) => {{
-        let gauge = Gauge::with_opts(Opts::new($name, $help))?;
+        let gauge = Gauge::with_opts($registry.opts($name, $help))?;
         $registry.register(Box::new(gauge.clone()))?;
         gauge
     }};
 }
 
+macro_rules! register_gauge_vec {
+    ($registry:expr, $name:expr, $help:expr, $labels:expr) => {{
+        let gauge_vec = GaugeVec::new($registry.opts($name, $help), $labels)?;
+        $registry.register(Box::new(gauge_vec.clone()))?;
+        gauge_vec
+    }};
+}
+
+/// Builds [`BitcoinMetrics`], registering only the gauges of the enabled sections.
+pub struct BitcoinMetricsBuilder {
+    registry: Registry,
+    node: Option<String>,
+    instance: Option<String>,
+    sections: BTreeSet<Section>,
+    extra: Vec<ExtraMetricConfig>,
+}
+
+impl BitcoinMetricsBuilder {
+    /// Registers on `registry` (e.g. one shared by several nodes) instead of a new one.
+    pub fn registry(mut self, registry: &Registry) -> Self {
+        self.registry = registry.clone();
+        self
+    }
+
+    /// Labels every metric `node="<alias>"`.
+    pub fn node(mut self, alias: Option<&str>) -> Self {
+        self.node = alias.map(str::to_string);
+        self
+    }
+
+    /// Labels every metric `instance="<instance>"`; see [`crate::config::NodeConfig::instance_label`].
+    pub fn instance(mut self, instance: Option<&str>) -> Self {
+        self.instance = instance.map(str::to_string);
+        self
+    }
+
+    /// Registers only these sections' metrics; all sections are registered by default.
+    pub fn sections(mut self, sections: impl IntoIterator<Item = Section>) -> Self {
+        self.sections = sections.into_iter().collect();
+        self
+    }
+
+    /// Also registers a gauge for each `[[extra_metric]]` entry.
+    pub fn extra_metrics(mut self, extra: &[ExtraMetricConfig]) -> Self {
+        self.extra = extra.to_vec();
+        self
+    }
+
+    pub fn build(self) -> Result<BitcoinMetrics, Error> {
+        let (node, instance) = (self.node.as_deref(), self.instance.as_deref());
+        BitcoinMetrics::build(&self.registry, node, instance, &self.sections, self.extra)
+    }
+}
+
 impl BitcoinMetrics {
+    /// All sections' metrics on a new registry.
     pub fn new() -> Result<Self, Error> {
-        let registry = Registry::new();
+        Self::builder().build()
+    }
+
+    pub fn builder() -> BitcoinMetricsBuilder {
+        BitcoinMetricsBuilder {
+            registry: Registry::new(),
+            node: None,
+            instance: None,
+            sections: Section::ALL.into_iter().collect(),
+            extra: Vec::new(),
+        }
+    }
+
+    /// Registers the metrics of newly enabled sections and unregisters those of disabled
+    /// ones, e.g. after a config reload.
+    pub fn set_sections(&self, sections: &BTreeSet<Section>) -> Result<(), Error> {
+        let mut registered = self.registered_sections.lock().expect("registered sections lock poisoned");
+        for (section, metrics) in &self.section_metrics {
+            match (registered.contains(section), sections.contains(section)) {
+                (false, true) => {
+                    for metric in metrics {
+                        self.registry.register(Box::new(Shared(metric.clone())))?;
+                    }
+                }
+                (true, false) => {
+                    for metric in metrics {
+                        self.registry.unregister(Box::new(Shared(metric.clone())))?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        *registered = sections.clone();
+        Ok(())
+    }
+
+    fn build(
+        shared: &Registry,
+        node: Option<&str>,
+        instance: Option<&str>,
+        sections: &BTreeSet<Section>,
+        extra: Vec<ExtraMetricConfig>,
+    ) -> Result<Self, Error> {
+        let registry = Registrar {
+            registry: shared,
+            const_labels: node_labels(node, instance),
+            sections,
+            current: Cell::new(None),
+            section_metrics: RefCell::new(BTreeMap::new()),
+        };
 
         // Blockchain info
+        registry.section(Section::Blockchain);
         let blocks = register_gauge!(registry, "bitcoin_blocks", "Current block height");
         let headers = register_gauge!(registry, "bitcoin_headers", "Current number of headers");
+        let headers_blocks_gap = register_gauge!(registry, "bitcoin_headers_blocks_gap", "Headers the node knows of beyond its validated blocks; shrinks to 0 as block download catches up");
         let difficulty = register_gauge!(registry, "bitcoin_difficulty", "Current mining difficulty");
+        let difficulty_log2 = register_gauge!(registry, "bitcoin_difficulty_log2", "Log2 of the current mining difficulty (negative below 1, e.g. on regtest)");
         let verification_progress = register_gauge!(registry, "bitcoin_verification_progress", "Estimate of verification progress [0..1]");
-        let size_on_disk = register_gauge!(registry, "bitcoin_size_on_disk_bytes", "Estimated size of the block and undo files on disk");
+        let size_on_disk = register_gauge!(registry, "bitcoin_size_on_disk_bytes", "Estimated size of the block and undo files on disk (a gauge: drops on pruning or reindex)");
+        // No labels; only exported once `disk_capacity_bytes` is configured
+        let disk_full_estimate_seconds = register_gauge_vec!(registry, "bitcoin_disk_full_estimate_seconds", "Estimated seconds until size_on_disk reaches disk_capacity_bytes at the recent growth rate (+Inf if not growing)", &[]);
         let initial_block_download = register_gauge!(registry, "bitcoin_initial_block_download", "Whether node is in initial block download (1=true, 0=false)");
+        let node_in_ibd = register_gauge!(registry, "bitcoin_node_in_ibd", "Whether the node is in initial block download, so sections meaningless until it completes are skipped (1=true, 0=false)");
+        let seconds_since_synced = register_gauge!(registry, "bitcoin_seconds_since_synced", "Seconds since the node was first seen out of initial block download, counted from exporter start if it already was (0 while in IBD)");
+        let node_synced = register_gauge!(registry, "bitcoin_node_synced", "Whether the node is out of initial block download with verification progress at or above synced_threshold (1=true, 0=false)");
         let chain_pruned = register_gauge!(registry, "bitcoin_chain_pruned", "Whether the blockchain is pruned (1=true, 0=false)");
+        let chain_work_log2 = register_gauge!(registry, "bitcoin_chain_work_log2", "Log2 of the total amount of work in the active chain");
+        // No labels; only exported once a node's `expected_chain` is configured
+        let chain_mismatch = register_gauge_vec!(registry, "bitcoin_chain_mismatch", "Whether the node is on a chain other than the configured expected_chain (1=mismatch, 0=ok)", &[]);
+        let best_block_timestamp = register_gauge!(registry, "bitcoin_best_block_timestamp_seconds", "Header timestamp of the best block (UNIX epoch)");
+        let best_block_info = register_gauge_vec!(registry, "bitcoin_best_block_info", "Hash of the best block (always 1); a different hash at the same height reveals a reorg", &["hash"]);
+        let reorgs = IntCounter::with_opts(
+            registry.opts("bitcoin_reorgs_total", "Number of tip changes that replaced blocks of the previous tip (a lower or equal height, or a previous tip no longer in the chain)"),
+        )?;
+        registry.register(Box::new(reorgs.clone()))?;
+        let block_timestamp_lag_seconds = register_gauge!(registry, "bitcoin_block_timestamp_lag_seconds", "Seconds since the best block's header timestamp, clamped at 0 for future-dated blocks");
+        // No labels; only exported once the tip has a parent
+        let last_block_interval_seconds = register_gauge_vec!(registry, "bitcoin_last_block_interval_seconds", "Seconds between the header timestamps of the best block and its parent (negative if dated before it)", &[]);
 
         // Mempool info
+        registry.section(Section::Mempool);
         let mempool_transactions = register_gauge!(registry, "bitcoin_mempool_transactions", "Current number of transactions in the mempool");
         let mempool_bytes = register_gauge!(registry, "bitcoin_mempool_bytes", "Sum of all virtual transaction sizes in the mempool");
         let mempool_usage = register_gauge!(registry, "bitcoin_mempool_usage_bytes", "Total memory usage for the mempool");
         let mempool_max_bytes = register_gauge!(registry, "bitcoin_mempool_max_bytes", "Maximum memory usage for the mempool");
         let mempool_min_fee = register_gauge!(registry, "bitcoin_mempool_min_fee_btc_per_kvb", "Minimum fee rate in BTC/kvB for tx to be accepted");
         let mempool_total_fee = register_gauge!(registry, "bitcoin_mempool_total_fee_btc", "Total fees of all transactions in the mempool in BTC");
+        let mempool_total_fee_sat = register_gauge!(registry, "bitcoin_mempool_total_fee_sat", "Total fees of all transactions in the mempool in satoshis");
         let mempool_min_relay_tx_fee = register_gauge!(registry, "bitcoin_mempool_min_relay_tx_fee_btc_per_kvb", "Minimum relay transaction fee in BTC/kvB");
+        let mempool_min_fee_above_relay = register_gauge!(registry, "bitcoin_mempool_min_fee_above_relay", "Whether the mempool minimum fee is above the minimum relay fee, i.e. the full mempool is evicting (1=true, 0=false)");
+        let mempool_min_fee_ratio = register_gauge!(registry, "bitcoin_mempool_min_fee_ratio", "Mempool minimum fee divided by the minimum relay fee (1 when the mempool isn't full)");
+        let mempool_headroom_bytes = register_gauge!(registry, "bitcoin_mempool_headroom_bytes", "Memory the mempool may still use before it starts evicting (maxmempool minus usage)");
+        let mempool_eviction_imminent = register_gauge!(registry, "bitcoin_mempool_eviction_imminent", "Whether the mempool headroom is below mempool_eviction_headroom_percent of maxmempool (1=true, 0=false)");
         let mempool_incremental_relay_fee = register_gauge!(registry, "bitcoin_mempool_incremental_relay_fee_btc_per_kvb", "Minimum fee rate increment for mempool limiting or BIP 125 replacement in BTC/kvB");
         let mempool_unbroadcast_count = register_gauge!(registry, "bitcoin_mempool_unbroadcast_count", "Number of transactions that haven't been broadcast yet");
         let mempool_full_rbf = register_gauge!(registry, "bitcoin_mempool_full_rbf", "Whether full replace-by-fee is enabled (1=true, 0=false)");
+        // No labels; only exported once `fee_pressure` is configured
+        let fee_pressure_index = register_gauge_vec!(registry, "bitcoin_fee_pressure_index", "Weighted mean of mempool fullness, minimum fee and 2-block fee estimate, each scaled to [0..1] (see collector.fee_pressure)", &[]);
+
+        // Mempool detail
+        registry.section(Section::MempoolDetail);
+        let mempool_tx_vsize_p50 = register_gauge!(registry, "bitcoin_mempool_tx_vsize_p50", "50th percentile virtual size of mempool transactions in vB");
+        let mempool_tx_vsize_p90 = register_gauge!(registry, "bitcoin_mempool_tx_vsize_p90", "90th percentile virtual size of mempool transactions in vB");
+        let mempool_tx_vsize_p99 = register_gauge!(registry, "bitcoin_mempool_tx_vsize_p99", "99th percentile virtual size of mempool transactions in vB");
+        let mempool_oldest_unbroadcast_seconds = register_gauge!(registry, "bitcoin_mempool_oldest_unbroadcast_seconds", "Age of the oldest mempool transaction not yet broadcast to any peer (0 when there are none)");
 
         // Network info
+        registry.section(Section::Network);
         let connections = register_gauge!(registry, "bitcoin_connections", "Total number of connections");
         let connections_in = register_gauge!(registry, "bitcoin_connections_in", "Number of inbound connections");
         let connections_out = register_gauge!(registry, "bitcoin_connections_out", "Number of outbound connections");
+        // No labels; only exported once a node's `max_connections` is configured
+        let connections_saturation_ratio = register_gauge_vec!(registry, "bitcoin_connections_saturation_ratio", "Connections as a fraction of the configured max_connections (1 = no room for more peers)", &[]);
         let network_active = register_gauge!(registry, "bitcoin_network_active", "Whether p2p networking is active (1=true, 0=false)");
+        let accepting_inbound = register_gauge!(registry, "bitcoin_node_accepting_inbound", "Whether the node advertises a local address with networking active, a proxy for being reachable from outside (1=true, 0=false)");
+        let local_addresses_count = register_gauge!(registry, "bitcoin_local_addresses_count", "Number of local addresses the node advertises to peers");
         let node_version = register_gauge!(registry, "bitcoin_version", "Bitcoin node version as integer");
         let protocol_version = register_gauge!(registry, "bitcoin_protocol_version", "Protocol version number");
+        let core_version_supported = register_gauge!(registry, "bitcoin_core_version_supported", "Whether the node's Bitcoin Core version is one this exporter supports (1=true, 0=false; upgrade the exporter if 0)");
         let time_offset = register_gauge!(registry, "bitcoin_time_offset_seconds", "Time offset from network median in seconds");
         let relay_fee = register_gauge!(registry, "bitcoin_relay_fee_btc_per_kvb", "Minimum relay fee for transactions in BTC/kvB");
         let incremental_fee = register_gauge!(registry, "bitcoin_incremental_fee_btc_per_kvb", "Minimum fee increment for mempool limiting in BTC/kvB");
+        let relay_fee_sat_per_vb = register_gauge!(registry, "bitcoin_relay_fee_sat_per_vb", "Minimum relay fee for transactions in sat/vB");
+        let incremental_fee_sat_per_vb = register_gauge!(registry, "bitcoin_incremental_fee_sat_per_vb", "Minimum fee increment for mempool limiting in sat/vB");
+        let network_reachable = register_gauge_vec!(registry, "bitcoin_network_reachable", "Whether the network (ipv4, ipv6, onion, i2p, cjdns) is reachable (1=true, 0=false)", &["network"]);
+        let network_proxy_configured = register_gauge_vec!(registry, "bitcoin_network_proxy_configured", "Whether a proxy is configured for the network, e.g. Tor for onion (1=true, 0=false)", &["network"]);
+        let network_limited = register_gauge_vec!(registry, "bitcoin_network_limited", "Whether the node is limited to other networks than this one, e.g. by -onlynet (1=true, 0=false)", &["network"]);
 
         // Peer info (aggregated)
+        registry.section(Section::Peers);
         let peer_count = register_gauge!(registry, "bitcoin_peer_count", "Number of connected peers");
         let peers_inbound = register_gauge!(registry, "bitcoin_peers_inbound", "Number of inbound peers");
         let peers_outbound = register_gauge!(registry, "bitcoin_peers_outbound", "Number of outbound peers");
+        let outbound_block_relay_connections = register_gauge!(registry, "bitcoin_outbound_block_relay_connections", "Number of outbound block-relay-only peers, which help protect against eclipse attacks");
         let peers_total_bytes_sent = register_gauge!(registry, "bitcoin_peers_total_bytes_sent", "Total bytes sent across all peers");
         let peers_total_bytes_received = register_gauge!(registry, "bitcoin_peers_total_bytes_received", "Total bytes received across all peers");
         let peers_avg_ping_seconds = register_gauge!(registry, "bitcoin_peers_avg_ping_seconds", "Average ping time across all peers in seconds");
+        let peers_avg_min_ping_seconds = register_gauge!(registry, "bitcoin_peers_avg_min_ping_seconds", "Average of each peer's minimum ping time in seconds, over peers with one recorded");
+        let peers_worst_min_ping_seconds = register_gauge!(registry, "bitcoin_peers_worst_min_ping_seconds", "Highest minimum ping time of any peer in seconds");
+        let peers_avg_connection_age_seconds = register_gauge!(registry, "bitcoin_peers_avg_connection_age_seconds", "Average time connected peers have been connected in seconds (low when connections churn)");
+        let peers_min_connection_age_seconds = register_gauge!(registry, "bitcoin_peers_min_connection_age_seconds", "Time the most recently connected peer has been connected in seconds");
+        let peers_min_fee_filter = register_gauge!(registry, "bitcoin_peers_min_fee_filter_btc_per_kvb", "Lowest fee filter advertised by any peer in BTC/kvB");
+        let peers_max_fee_filter = register_gauge!(registry, "bitcoin_peers_max_fee_filter_btc_per_kvb", "Highest fee filter advertised by any peer in BTC/kvB");
+        let peers_max_synced_headers = register_gauge!(registry, "bitcoin_peers_max_synced_headers", "Highest header height reported by any peer (synced headers or starting height)");
+        let peers_addresses_processed = register_gauge!(registry, "bitcoin_peers_addresses_processed_total", "Addresses processed from addr gossip, summed over connected peers that report it (drops as peers disconnect)");
+        let peers_addresses_rate_limited = register_gauge!(registry, "bitcoin_peers_addresses_rate_limited_total", "Addresses dropped by addr rate limiting, summed over connected peers that report it (drops as peers disconnect)");
+        let peers_by_transport = register_gauge_vec!(registry, "bitcoin_peers_by_transport", "Number of connected peers per transport protocol (v1, v2 for BIP324 encrypted, detecting)", &["transport"]);
+        let blocks_behind_peers = register_gauge!(registry, "bitcoin_blocks_behind_peers", "Blocks between the local tip and the highest header reported by peers (0 when not behind)");
+        // No labels; only exported while some connected peer has relayed a block
+        let peers_oldest_last_block_seconds = register_gauge_vec!(registry, "bitcoin_peers_oldest_last_block_seconds", "Seconds since a new block was last received from any connected peer", &[]);
+        let peers_by_network = register_gauge_vec!(registry, "bitcoin_peers_by_network", "Number of connected peers per network (ipv4, ipv6, onion, i2p, cjdns, not_publicly_routable)", &["network"]);
+        // No labels; only exported when both the network and peers sections were collected
+        let peers_on_limited_networks = register_gauge_vec!(registry, "bitcoin_peers_on_limited_networks", "Number of connected peers on a network reported as limited, e.g. leaking past -onlynet", &[]);
+
+        // Peer detail (opt-in)
+        registry.section(Section::PeerDetail);
+        let peer_bytes_sent = register_gauge_vec!(registry, "bitcoin_peer_bytes_sent", "Bytes sent to the peer (peer=\"others\" sums the peers beyond max_peer_series)", &["peer"]);
+        let peer_bytes_received = register_gauge_vec!(registry, "bitcoin_peer_bytes_received", "Bytes received from the peer (peer=\"others\" sums the peers beyond max_peer_series)", &["peer"]);
+        let peer_series_truncated = register_gauge!(registry, "bitcoin_peer_series_truncated", "Whether more peers are connected than max_peer_series, so some are summed into peer=\"others\" (1=true, 0=false)");
 
         // Mining info
+        registry.section(Section::Mining);
         let network_hash_ps = register_gauge!(registry, "bitcoin_network_hash_per_second", "Estimated network hashes per second");
         let mining_pooled_tx = register_gauge!(registry, "bitcoin_mining_pooled_transactions", "Number of transactions in the mining pool");
 
         // Chain tx stats
+        registry.section(Section::ChainTxStats);
         let chain_tx_count = register_gauge!(registry, "bitcoin_chain_tx_count", "Total number of transactions in the chain");
         let chain_tx_rate = register_gauge!(registry, "bitcoin_chain_tx_rate_per_second", "Average transaction rate per second over the window");
         let chain_tx_window_block_count = register_gauge!(registry, "bitcoin_chain_tx_window_block_count", "Number of blocks in the stats window");
         let chain_tx_window_tx_count = register_gauge!(registry, "bitcoin_chain_tx_window_tx_count", "Number of transactions in the stats window");
         let chain_tx_window_interval = register_gauge!(registry, "bitcoin_chain_tx_window_interval_seconds", "Elapsed time of the stats window in seconds");
+        let chain_tx_per_day = register_gauge!(registry, "bitcoin_chain_tx_per_day", "Transactions per day over the stats window (window tx count scaled from the window interval to 86400s)");
 
         // Net totals
+        registry.section(Section::NetTotals);
         let net_total_bytes_received = register_gauge!(registry, "bitcoin_net_total_bytes_received", "Total bytes received since node start");
         let net_total_bytes_sent = register_gauge!(registry, "bitcoin_net_total_bytes_sent", "Total bytes sent since node start");
+        // No labels; only exported once two scrapes have been seen
+        let net_bytes_received_per_second = register_gauge_vec!(registry, "bitcoin_net_bytes_received_per_second", "Bytes received per second between the last two scrapes", &[]);
+        let net_bytes_sent_per_second = register_gauge_vec!(registry, "bitcoin_net_bytes_sent_per_second", "Bytes sent per second between the last two scrapes", &[]);
 
         // Fee estimation
+        registry.section(Section::FeeEstimates);
         let fee_estimate_2_blocks = register_gauge!(registry, "bitcoin_fee_estimate_2_blocks_btc_per_kvb", "Estimated fee rate for confirmation within 2 blocks in BTC/kvB");
         let fee_estimate_6_blocks = register_gauge!(registry, "bitcoin_fee_estimate_6_blocks_btc_per_kvb", "Estimated fee rate for confirmation within 6 blocks in BTC/kvB");
         let fee_estimate_12_blocks = register_gauge!(registry, "bitcoin_fee_estimate_12_blocks_btc_per_kvb", "Estimated fee rate for confirmation within 12 blocks in BTC/kvB");
         let fee_estimate_144_blocks = register_gauge!(registry, "bitcoin_fee_estimate_144_blocks_btc_per_kvb", "Estimated fee rate for confirmation within 144 blocks in BTC/kvB");
+        let fee_estimate_available = register_gauge_vec!(registry, "bitcoin_fee_estimate_available", "Whether the node could estimate a fee rate for the confirmation target (1=true, 0=false: the fee gauge holds a stale value)", &["target"]);
+        let fee_estimate_fallback = register_gauge_vec!(registry, "bitcoin_fee_estimate_fallback", "Whether the node reported errors along with the fee rate for the confirmation target, making it a best guess rather than a confident estimate (1=true, 0=false)", &["target"]);
+        let fee_estimate_last_update = register_gauge_vec!(registry, "bitcoin_fee_estimate_last_update_timestamp", "When the fee estimate for the confirmation target was last refreshed with a rate (UNIX epoch)", &["target"]);
 
         // Chain tips
+        registry.section(Section::ChainTips);
         let chain_tips_count = register_gauge!(registry, "bitcoin_chain_tips_count", "Number of known chain tips (forks)");
 
         // Uptime
+        registry.section(Section::Uptime);
         let node_uptime_seconds = register_gauge!(registry, "bitcoin_node_uptime_seconds", "Node uptime in seconds");
+        let node_start_timestamp = register_gauge!(registry, "bitcoin_node_start_timestamp", "When the node process started (UNIX epoch, scrape time minus uptime); changes on a restart");
 
         // Latest block stats
+        registry.section(Section::BlockStats);
+        let latest_block_height = register_gauge!(registry, "bitcoin_latest_block_height", "Height of the block the latest-block gauges describe");
         let latest_block_txs = register_gauge!(registry, "bitcoin_latest_block_transactions", "Number of transactions in the latest block");
         let latest_block_size = register_gauge!(registry, "bitcoin_latest_block_size_bytes", "Total size of the latest block in bytes");
         let latest_block_weight = register_gauge!(registry, "bitcoin_latest_block_weight", "Total weight of the latest block");
@@ -202,50 +564,179 @@ impl BitcoinMetrics {
         let latest_block_segwit_total_weight = register_gauge!(registry, "bitcoin_latest_block_segwit_total_weight", "Total weight of segwit transactions in the latest block");
         let latest_block_total_out = register_gauge!(registry, "bitcoin_latest_block_total_out_sat", "Total output value in the latest block in satoshis (excluding coinbase)");
         let latest_block_utxo_increase = register_gauge!(registry, "bitcoin_latest_block_utxo_increase", "Change in UTXO count from the latest block");
+        let latest_block_utxo_size_increase = register_gauge!(registry, "bitcoin_latest_block_utxo_size_increase", "Change in UTXO set size from the latest block in bytes");
+        let latest_block_utxo_increase_actual = register_gauge!(registry, "bitcoin_latest_block_utxo_increase_actual", "Change in UTXO count from the latest block, not counting unspendable outputs");
+        let latest_block_utxo_size_increase_actual = register_gauge!(registry, "bitcoin_latest_block_utxo_size_increase_actual", "Change in UTXO set size from the latest block in bytes, not counting unspendable outputs");
+        let latest_block_avg_tx_size = register_gauge!(registry, "bitcoin_latest_block_avg_tx_size", "Average transaction size in the latest block in bytes (excluding coinbase)");
+        let latest_block_median_tx_size = register_gauge!(registry, "bitcoin_latest_block_median_tx_size", "Median transaction size in the latest block in bytes (excluding coinbase)");
+        let latest_block_max_tx_size = register_gauge!(registry, "bitcoin_latest_block_max_tx_size", "Largest transaction size in the latest block in bytes (excluding coinbase)");
+        let latest_block_min_tx_size = register_gauge!(registry, "bitcoin_latest_block_min_tx_size", "Smallest transaction size in the latest block in bytes (excluding coinbase)");
         let latest_block_fee_rate_10th = register_gauge!(registry, "bitcoin_latest_block_fee_rate_10th_percentile_sat_per_vb", "10th percentile fee rate in the latest block in sat/vB");
         let latest_block_fee_rate_25th = register_gauge!(registry, "bitcoin_latest_block_fee_rate_25th_percentile_sat_per_vb", "25th percentile fee rate in the latest block in sat/vB");
         let latest_block_fee_rate_50th = register_gauge!(registry, "bitcoin_latest_block_fee_rate_50th_percentile_sat_per_vb", "50th percentile (median) fee rate in the latest block in sat/vB");
         let latest_block_fee_rate_75th = register_gauge!(registry, "bitcoin_latest_block_fee_rate_75th_percentile_sat_per_vb", "75th percentile fee rate in the latest block in sat/vB");
         let latest_block_fee_rate_90th = register_gauge!(registry, "bitcoin_latest_block_fee_rate_90th_percentile_sat_per_vb", "90th percentile fee rate in the latest block in sat/vB");
 
-        // Collector meta
+        // Latest block coinbase (opt-in)
+        registry.section(Section::Coinbase);
+        let latest_block_coinbase_value = register_gauge!(registry, "bitcoin_latest_block_coinbase_value_sat", "Total output value of the latest block's coinbase (subsidy plus fees claimed) in satoshis");
+        let latest_block_pool = register_gauge_vec!(registry, "bitcoin_latest_block_pool_info", "Mining pool identified from the latest block's coinbase tag (\"unknown\" when none matches)", &["pool"]);
+
+        // Chainstates (assumeutxo)
+        registry.section(Section::Chainstates);
+        let chainstate_blocks = register_gauge_vec!(registry, "bitcoin_chainstate_blocks", "Number of blocks in each chainstate", &["chainstate"]);
+        let chainstate_validated = register_gauge_vec!(registry, "bitcoin_chainstate_validated", "Whether each chainstate is fully validated (1=true, 0=false)", &["chainstate"]);
+
+        // Node warnings
+        registry.section(Section::Warnings);
+        let warnings_active = register_gauge!(registry, "bitcoin_warnings_active", "Whether the node reports any warnings (1=true, 0=false)");
+        let warning_info = register_gauge_vec!(registry, "bitcoin_warning_info", "Warning currently reported by the node", &["warning"]);
+
+        // Wallets (only exported for a node's configured `wallets`)
+        registry.always();
+        let wallet_balance = register_gauge_vec!(registry, "bitcoin_wallet_balance_btc", "Confirmed balance of the wallet in BTC", &["wallet"]);
+        let wallet_unconfirmed_balance = register_gauge_vec!(registry, "bitcoin_wallet_unconfirmed_balance_btc", "Unconfirmed balance of the wallet in BTC", &["wallet"]);
+        let wallet_immature_balance = register_gauge_vec!(registry, "bitcoin_wallet_immature_balance_btc", "Immature coinbase balance of the wallet in BTC", &["wallet"]);
+        let wallet_tx_count = register_gauge_vec!(registry, "bitcoin_wallet_transactions", "Number of transactions in the wallet", &["wallet"]);
+        let wallet_errors = IntCounterVec::new(
+            registry.opts("bitcoin_wallet_errors_total", "Number of failed getwalletinfo calls, e.g. for a wallet that isn't loaded"),
+            &["wallet"],
+        )?;
+        registry.register(Box::new(wallet_errors.clone()))?;
+
+        // Watched descriptors (only exported for a node's configured `watch_descriptors`)
+        let watched_descriptor_balance = register_gauge_vec!(registry, "bitcoin_watched_descriptor_balance_btc", "Balance of the unspent outputs matching the watched descriptor in BTC, as of its latest scantxoutset", &["descriptor"]);
+        let watched_descriptor_scan_errors = IntCounterVec::new(
+            registry.opts("bitcoin_watched_descriptor_scan_errors_total", "Number of scantxoutset calls for the watched descriptor that failed or were aborted"),
+            &["descriptor"],
+        )?;
+        registry.register(Box::new(watched_descriptor_scan_errors.clone()))?;
+
+        // Collector meta (always registered)
+        registry.always();
         let scrape_duration_seconds = register_gauge!(registry, "bitcoin_collector_last_scrape_duration_seconds", "Duration of the last metrics collection in seconds");
+        // Doubling buckets from 10ms, capped at 30s
+        let mut buckets = prometheus::exponential_buckets(0.01, 2.0, 12)?;
+        buckets.push(30.0);
+        let scrape_duration_histogram = Histogram::with_opts(
+            HistogramOpts::from(registry.opts(
+                "bitcoin_collector_scrape_duration_histogram",
+                "Distribution of metrics collection durations in seconds",
+            ))
+            .buckets(buckets),
+        )?;
+        registry.register(Box::new(scrape_duration_histogram.clone()))?;
         let scrape_error = register_gauge!(registry, "bitcoin_collector_last_scrape_error", "Whether the last scrape had an error (1=error, 0=ok)");
+        // No labels; only exported once the node has accepted or rejected the credentials
+        let node_rpc_auth_ok = register_gauge_vec!(registry, "bitcoin_node_rpc_auth_ok", "Whether the node accepted the RPC credentials on the last collection that reached it (1=accepted, 0=rejected with 401/403)", &[]);
+        let scrape_timed_out = register_gauge!(registry, "bitcoin_collector_scrape_timed_out", "Whether the last scrape hit scrape_timeout_seconds and served the previous values (1=timed out, 0=ok)");
+        let seconds_since_last_scrape = register_gauge!(registry, "bitcoin_collector_seconds_since_last_scrape", "Seconds between the starts of the previous and the current collection (0 until the second one)");
+        let scrapes_in_progress = register_gauge!(registry, "bitcoin_collector_scrapes_in_progress", "Number of collections currently running; above 1 means collections overlap");
+        let section_stale = register_gauge_vec!(registry, "bitcoin_collector_section_stale", "Whether an enabled section's metrics kept previous values because this collection failed to refresh them (1=stale, 0=fresh)", &["section"]);
+        let last_error_info = register_gauge_vec!(registry, "bitcoin_collector_last_error_info", "Most recent error of an RPC method (truncated), until the method next succeeds", &["method", "error"]);
+
+        // Extra metrics (`[[extra_metric]]`)
+        let extra = extra
+            .into_iter()
+            .map(|config| {
+                let gauge = register_gauge!(registry, &config.name, &config.help);
+                Ok(ExtraMetric { config, gauge })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let extra_metric_errors = IntCounterVec::new(
+            registry.opts(
+                "bitcoin_collector_extra_metric_errors_total",
+                "Number of times an extra_metric could not be read (RPC failure or no number at its json_pointer)",
+            ),
+            &["metric"],
+        )?;
+        registry.register(Box::new(extra_metric_errors.clone()))?;
 
         Ok(Self {
-            registry,
+            registry: shared.clone(),
+            node: node.map(str::to_string),
             blocks,
             headers,
+            headers_blocks_gap,
             difficulty,
+            difficulty_log2,
             verification_progress,
             size_on_disk,
+            disk_full_estimate_seconds,
             initial_block_download,
+            node_synced,
+            node_in_ibd,
+            seconds_since_synced,
             chain_pruned,
+            chain_work_log2,
+            chain_mismatch,
+            best_block_timestamp,
+            best_block_info,
+            reorgs,
+            block_timestamp_lag_seconds,
+            last_block_interval_seconds,
             mempool_transactions,
             mempool_bytes,
             mempool_usage,
             mempool_max_bytes,
             mempool_min_fee,
             mempool_total_fee,
+            mempool_total_fee_sat,
             mempool_min_relay_tx_fee,
+            mempool_min_fee_above_relay,
+            mempool_min_fee_ratio,
+            mempool_headroom_bytes,
+            mempool_eviction_imminent,
             mempool_incremental_relay_fee,
             mempool_unbroadcast_count,
             mempool_full_rbf,
+            fee_pressure_index,
+            mempool_tx_vsize_p50,
+            mempool_tx_vsize_p90,
+            mempool_tx_vsize_p99,
+            mempool_oldest_unbroadcast_seconds,
             connections,
             connections_in,
             connections_out,
+            connections_saturation_ratio,
             network_active,
+            accepting_inbound,
+            local_addresses_count,
             node_version,
             protocol_version,
+            core_version_supported,
             time_offset,
             relay_fee,
             incremental_fee,
+            relay_fee_sat_per_vb,
+            incremental_fee_sat_per_vb,
+            network_reachable,
+            network_proxy_configured,
+            network_limited,
             peer_count,
             peers_inbound,
             peers_outbound,
+            outbound_block_relay_connections,
             peers_total_bytes_sent,
             peers_total_bytes_received,
             peers_avg_ping_seconds,
+            peers_avg_min_ping_seconds,
+            peers_worst_min_ping_seconds,
+            peers_avg_connection_age_seconds,
+            peers_min_connection_age_seconds,
+            peers_min_fee_filter,
+            peers_max_fee_filter,
+            peers_max_synced_headers,
+            peers_addresses_processed,
+            peers_addresses_rate_limited,
+            peers_by_transport,
+            blocks_behind_peers,
+            peers_oldest_last_block_seconds,
+            peers_by_network,
+            peers_on_limited_networks,
+            peer_bytes_sent,
+            peer_bytes_received,
+            peer_series_truncated,
             network_hash_ps,
             mining_pooled_tx,
             chain_tx_count,
@@ -253,14 +744,22 @@ impl BitcoinMetrics {
             chain_tx_window_block_count,
             chain_tx_window_tx_count,
             chain_tx_window_interval,
+            chain_tx_per_day,
             net_total_bytes_received,
             net_total_bytes_sent,
+            net_bytes_received_per_second,
+            net_bytes_sent_per_second,
             fee_estimate_2_blocks,
             fee_estimate_6_blocks,
             fee_estimate_12_blocks,
             fee_estimate_144_blocks,
+            fee_estimate_available,
+            fee_estimate_fallback,
+            fee_estimate_last_update,
             chain_tips_count,
             node_uptime_seconds,
+            node_start_timestamp,
+            latest_block_height,
             latest_block_txs,
             latest_block_size,
             latest_block_weight,
@@ -280,13 +779,51 @@ impl BitcoinMetrics {
             latest_block_segwit_total_weight,
             latest_block_total_out,
             latest_block_utxo_increase,
+            latest_block_utxo_size_increase,
+            latest_block_utxo_increase_actual,
+            latest_block_utxo_size_increase_actual,
+            latest_block_avg_tx_size,
+            latest_block_median_tx_size,
+            latest_block_max_tx_size,
+            latest_block_min_tx_size,
             latest_block_fee_rate_10th,
             latest_block_fee_rate_25th,
             latest_block_fee_rate_50th,
             latest_block_fee_rate_75th,
             latest_block_fee_rate_90th,
+            latest_block_coinbase_value,
+            latest_block_pool,
+            chainstate_blocks,
+            chainstate_validated,
+            warnings_active,
+            warning_info,
+            wallet_balance,
+            wallet_unconfirmed_balance,
+            wallet_immature_balance,
+            wallet_tx_count,
+            wallet_errors,
+            watched_descriptor_balance,
+            watched_descriptor_scan_errors,
             scrape_duration_seconds,
+            scrape_duration_histogram,
             scrape_error,
+            node_rpc_auth_ok,
+            scrape_timed_out,
+            seconds_since_last_scrape,
+            scrapes_in_progress,
+            last_error_info,
+            section_stale,
+            extra,
+            extra_metric_errors,
+            section_metrics: registry.section_metrics.into_inner(),
+            registered_sections: Mutex::new(sections.clone()),
         })
     }
 }
+
+/// Const labels identifying a node's series; empty for a single unnamed node without an
+/// `instance` label.
+pub fn node_labels(node: Option<&str>, instance: Option<&str>) -> HashMap<String, String> {
+    let labels = [("node", node), ("instance", instance)];
+    labels.into_iter().filter_map(|(name, value)| Some((name.to_string(), value?.to_string()))).collect()
+}
@@ -0,0 +1,715 @@
+//! A configurable [`NodeClient`] for tests, available with the `testing` feature.
+//!
+//! [`MockNodeClient::new`] answers every RPC from a mainnet-like fixture (tip at height
+//! 800,000, two peers, a small mempool); the `with_*` builders adjust individual responses
+//! and [`MockNodeClient::with_error`] makes a single RPC fail.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use corepc_client::types::v28::*;
+
+use crate::Error;
+use crate::transport::TransportError;
+use crate::node::{
+    BlockHeader, BlockStats, BlockTxids, ChainTxStats, MiningInfo, NodeClient, RawTransaction, RawTxInput, RawTxOutput,
+    ScanTxOutSet, WalletInfo,
+};
+
+pub struct MockNodeClient {
+    blockchain_info: GetBlockchainInfo,
+    mempool_info: GetMempoolInfo,
+    network_info: GetNetworkInfo,
+    peer_info: GetPeerInfo,
+    mining_info: MiningInfo,
+    chain_tx_stats: ChainTxStats,
+    net_totals: GetNetTotals,
+    chain_tips: GetChainTips,
+    block_stats: BlockStats,
+    chain_states: GetChainStates,
+    raw_mempool: GetRawMempoolVerbose,
+    block_txids: BlockTxids,
+    /// `getblockheader` responses by block hash; any other hash is unknown to the node.
+    block_headers: BTreeMap<String, BlockHeader>,
+    coinbase: RawTransaction,
+    uptime: u32,
+    /// Fee rate per confirmation target; `None` answers like a node without enough data.
+    fee_rates: BTreeMap<u32, Option<f64>>,
+    /// `errors` reported alongside a fee rate, per confirmation target.
+    fee_errors: BTreeMap<u32, Vec<String>>,
+    /// Loaded wallets by name; any other wallet fails like an unloaded one.
+    wallets: BTreeMap<String, WalletInfo>,
+    /// Balance `scantxoutset` finds per descriptor; any other descriptor fails to parse.
+    descriptor_balances: BTreeMap<String, f64>,
+    /// Untyped results for `call_json`, by RPC method.
+    json_responses: BTreeMap<String, serde_json::Value>,
+    /// RPC method name to the error message it fails with.
+    errors: BTreeMap<String, String>,
+    outage: AtomicBool,
+    unauthorized: AtomicBool,
+}
+
+/// A mempool entry with the given vsize, entry time and unbroadcast flag.
+pub fn mempool_entry(vsize: i64, time: i64, unbroadcast: bool) -> MempoolEntry {
+    MempoolEntry {
+        vsize,
+        weight: vsize * 4,
+        time,
+        height: 799_999,
+        descendant_count: 1,
+        descendant_size: vsize,
+        ancestor_count: 1,
+        ancestor_size: vsize,
+        wtxid: String::new(),
+        fees: MempoolEntryFees {
+            base: 0.00001,
+            modified: 0.00001,
+            ancestor: 0.00001,
+            descendant: 0.00001,
+        },
+        depends: vec![],
+        spent_by: vec![],
+        bip125_replaceable: false,
+        unbroadcast,
+    }
+}
+
+impl Default for MockNodeClient {
+    fn default() -> Self {
+        Self {
+            blockchain_info: GetBlockchainInfo {
+                chain: String::from("main"),
+                blocks: 800000,
+                headers: 800000,
+                best_block_hash: String::from(
+                    "0000000000000000000000000000000000000000000000000000000000000000"
+                ),
+                difficulty: 53_911_173_001_054.59,
+                time: 1_700_000_000,
+                median_time: 1_699_999_000,
+                verification_progress: 0.9999,
+                initial_block_download: false,
+                chain_work: "000000000000000000000000000000000000000052b2559353df4117b7348b64".into(),
+                size_on_disk: 600_000_000_000,
+                pruned: false,
+                prune_height: None,
+                automatic_pruning: None,
+                prune_target_size: None,
+                softforks: Default::default(),
+                warnings: vec![],
+            },
+            mempool_info: GetMempoolInfo {
+                loaded: true,
+                size: 5000,
+                bytes: 3_000_000,
+                usage: 10_000_000,
+                total_fee: 0.5,
+                max_mempool: 300_000_000,
+                mempool_min_fee: 0.00001,
+                min_relay_tx_fee: 0.00001,
+                incremental_relay_fee: 0.00001,
+                unbroadcast_count: 3,
+                full_rbf: false,
+            },
+            network_info: GetNetworkInfo {
+                version: 280100,
+                subversion: String::from("/Satoshi:28.1.0/"),
+                protocol_version: 70016,
+                local_services: String::new(),
+                local_services_names: vec![],
+                local_relay: true,
+                time_offset: -2,
+                connections: 125,
+                connections_in: 85,
+                connections_out: 40,
+                network_active: true,
+                networks: vec![
+                    GetNetworkInfoNetwork {
+                        name: "ipv4".into(),
+                        limited: false,
+                        reachable: true,
+                        proxy: String::new(),
+                        proxy_randomize_credentials: false,
+                    },
+                    GetNetworkInfoNetwork {
+                        name: "onion".into(),
+                        limited: true,
+                        reachable: false,
+                        proxy: String::new(),
+                        proxy_randomize_credentials: false,
+                    },
+                ],
+                relay_fee: 0.00001,
+                incremental_fee: 0.00001,
+                local_addresses: vec![GetNetworkInfoAddress { address: "203.0.113.5".into(), port: 8333, score: 4 }],
+                warnings: vec!["This is a pre-release test build - use at your own risk".into(), String::new()],
+            },
+            peer_info: GetPeerInfo(vec![
+                PeerInfo {
+                    id: 1,
+                    address: "1.2.3.4:8333".into(),
+                    address_bind: Some("0.0.0.0:0".into()),
+                    address_local: None,
+                    network: "ipv4".into(),
+                    mapped_as: None,
+                    services: "0000000000000409".into(),
+                    services_names: vec!["NETWORK".into(), "WITNESS".into()],
+                    relay_transactions: true,
+                    last_send: 1_700_000_000,
+                    last_received: 1_700_000_000,
+                    last_transaction: 0,
+                    last_block: 0,
+                    bytes_sent: 50_000,
+                    bytes_received: 100_000,
+                    connection_time: 1_699_900_000,
+                    time_offset: 0,
+                    ping_time: Some(0.05),
+                    minimum_ping: Some(0.02),
+                    ping_wait: None,
+                    version: 70016,
+                    subversion: "/Satoshi:25.0.0/".into(),
+                    inbound: false,
+                    bip152_hb_to: false,
+                    bip152_hb_from: false,
+                    add_node: None,
+                    starting_height: Some(799_990),
+                    presynced_headers: Some(-1),
+                    ban_score: None,
+                    synced_headers: Some(800_000),
+                    synced_blocks: Some(800_000),
+                    inflight: Some(vec![]),
+                    addresses_relay_enabled: None,
+                    addresses_processed: Some(1_500),
+                    addresses_rate_limited: Some(30),
+                    permissions: vec![],
+                    whitelisted: None,
+                    minimum_fee_filter: 0.00001,
+                    bytes_sent_per_message: Default::default(),
+                    bytes_received_per_message: Default::default(),
+                    connection_type: Some("outbound-full-relay".into()),
+                    transport_protocol_type: "v1".into(),
+                    session_id: String::new(),
+                },
+                PeerInfo {
+                    id: 2,
+                    address: "5.6.7.8:8333".into(),
+                    address_bind: Some("0.0.0.0:0".into()),
+                    address_local: None,
+                    network: "ipv4".into(),
+                    mapped_as: None,
+                    services: "0000000000000409".into(),
+                    services_names: vec!["NETWORK".into(), "WITNESS".into()],
+                    relay_transactions: true,
+                    last_send: 1_700_000_000,
+                    last_received: 1_700_000_000,
+                    last_transaction: 0,
+                    last_block: 0,
+                    bytes_sent: 30_000,
+                    bytes_received: 60_000,
+                    connection_time: 1_699_900_000,
+                    time_offset: 0,
+                    ping_time: Some(0.10),
+                    minimum_ping: Some(0.05),
+                    ping_wait: None,
+                    version: 70016,
+                    subversion: "/Satoshi:25.0.0/".into(),
+                    inbound: true,
+                    bip152_hb_to: false,
+                    bip152_hb_from: false,
+                    add_node: None,
+                    starting_height: Some(799_990),
+                    presynced_headers: Some(-1),
+                    ban_score: None,
+                    synced_headers: Some(800_002),
+                    synced_blocks: Some(800_000),
+                    inflight: Some(vec![]),
+                    addresses_relay_enabled: None,
+                    addresses_processed: None,
+                    addresses_rate_limited: None,
+                    permissions: vec![],
+                    whitelisted: None,
+                    minimum_fee_filter: 0.0001,
+                    bytes_sent_per_message: Default::default(),
+                    bytes_received_per_message: Default::default(),
+                    connection_type: Some("inbound".into()),
+                    transport_protocol_type: "v2".into(),
+                    session_id: String::new(),
+                },
+            ]),
+            mining_info: MiningInfo {
+                blocks: 800_000,
+                current_block_weight: Some(3_993_000),
+                current_block_tx: Some(2_500),
+                difficulty: 53_911_173_001_054.59,
+                network_hash_ps: 4.5e17,
+                pooled_tx: 5000,
+                chain: "main".into(),
+                warnings: vec![],
+            },
+            chain_tx_stats: ChainTxStats {
+                time: 1_700_000_000,
+                tx_count: 900_000_000,
+                window_final_block_hash: "0000000000000000000000000000000000000000000000000000000000000000".into(),
+                window_final_block_height: 800_000,
+                window_block_count: 4032,
+                window_tx_count: Some(12_000_000),
+                window_interval: Some(2_419_200),
+                tx_rate: Some(4.96),
+            },
+            net_totals: GetNetTotals {
+                total_bytes_received: 5_000_000_000,
+                total_bytes_sent: 3_000_000_000,
+                time_millis: 1_700_000_000_000,
+                upload_target: UploadTarget {
+                    timeframe: 86400,
+                    target: 0,
+                    target_reached: false,
+                    serve_historical_blocks: true,
+                    bytes_left_in_cycle: 0,
+                    time_left_in_cycle: 43200,
+                },
+            },
+            chain_tips: GetChainTips(vec![
+                ChainTips {
+                    height: 800_000,
+                    hash: "0000000000000000000000000000000000000000000000000000000000000000".into(),
+                    branch_length: 0,
+                    status: ChainTipsStatus::Active,
+                },
+                ChainTips {
+                    height: 799_998,
+                    hash: "0000000000000000000000000000000000000000000000000000000000000001".into(),
+                    branch_length: 2,
+                    status: ChainTipsStatus::ValidFork,
+                },
+            ]),
+            block_stats: BlockStats {
+                txs: Some(2500),
+                total_size: Some(2_000_000),
+                total_weight: Some(3_993_000),
+                average_fee: Some(15_000),
+                average_fee_rate: Some(25),
+                median_fee: Some(10_000),
+                minimum_fee: Some(500),
+                max_fee: Some(500_000),
+                minimum_fee_rate: Some(1),
+                max_fee_rate: Some(200),
+                total_fee: Some(37_500_000),
+                subsidy: Some(625_000_000),
+                inputs: Some(6000),
+                outputs: Some(8000),
+                segwit_txs: Some(2000),
+                segwit_total_size: Some(1_500_000),
+                segwit_total_weight: Some(3_000_000),
+                total_out: Some(500_000_000_000),
+                utxo_increase: Some(500),
+                utxo_size_increase: Some(40_000),
+                utxo_increase_actual: Some(490),
+                utxo_size_increase_actual: Some(39_200),
+                average_tx_size: Some(400),
+                median_tx_size: Some(250),
+                max_tx_size: Some(90_000),
+                minimum_tx_size: Some(150),
+                fee_rate_percentiles: Some([5, 10, 20, 50, 100]),
+            },
+            chain_states: GetChainStates {
+                headers: 800_000,
+                chain_states: vec![
+                    ChainState {
+                        blocks: 400_000,
+                        best_block_hash: "0000000000000000000000000000000000000000000000000000000000000002".into(),
+                        difficulty: 163_491_654_908.96,
+                        verification_progress: 0.25,
+                        snapshot_block_hash: None,
+                        coins_db_cache_bytes: 8_388_608,
+                        coins_tip_cache_bytes: 444_596_224,
+                        validated: false,
+                    },
+                    ChainState {
+                        blocks: 800_000,
+                        best_block_hash: "0000000000000000000000000000000000000000000000000000000000000000".into(),
+                        difficulty: 53_911_173_001_054.59,
+                        verification_progress: 0.9999,
+                        snapshot_block_hash: Some("0000000000000000000000000000000000000000000000000000000000000003".into()),
+                        coins_db_cache_bytes: 8_388_608,
+                        coins_tip_cache_bytes: 444_596_224,
+                        validated: false,
+                    },
+                ],
+            },
+            raw_mempool: {
+                let vsizes = [110, 140, 141, 150, 200, 225, 250, 400, 1_000, 50_000];
+                GetRawMempoolVerbose(
+                    vsizes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &vsize)| (format!("{i:064x}"), mempool_entry(vsize, 1_699_999_000 + i as i64, false)))
+                        .collect(),
+                )
+            },
+            block_txids: BlockTxids {
+                tx: vec![
+                    "00000000000000000000000000000000000000000000000000000000000000cb".into(),
+                    "0000000000000000000000000000000000000000000000000000000000000001".into(),
+                ],
+            },
+            // The tip came 600 seconds after its parent
+            block_headers: BTreeMap::from([
+                (
+                    format!("{:064x}", 0),
+                    BlockHeader { time: 1_700_000_000, previous_block_hash: Some(format!("{:064x}", 799_999)) },
+                ),
+                (
+                    format!("{:064x}", 799_999),
+                    BlockHeader { time: 1_699_999_400, previous_block_hash: Some(format!("{:064x}", 799_998)) },
+                ),
+            ]),
+            // Pays the 6.25 BTC subsidy plus 0.375 BTC fees, tagged by Foundry
+            coinbase: RawTransaction {
+                vin: vec![RawTxInput { coinbase: Some("0300350c2f466f756e6472792055534120506f6f6c202364726f70676f6c642f".into()) }],
+                vout: vec![RawTxOutput { value: 6.625 }, RawTxOutput { value: 0.0 }],
+            },
+            uptime: 86400,
+            fee_rates: BTreeMap::from([(2, Some(0.00025)), (6, Some(0.00015)), (12, Some(0.00010)), (144, Some(0.00005))]),
+            fee_errors: BTreeMap::new(),
+            wallets: BTreeMap::new(),
+            descriptor_balances: BTreeMap::new(),
+            json_responses: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            outage: AtomicBool::new(false),
+            unauthorized: AtomicBool::new(false),
+        }
+    }
+}
+
+impl MockNodeClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers like a freshly started regtest node: only the genesis block, no peers, an
+    /// empty mempool and no fee data. Like the node, it can't give block stats or the
+    /// coinbase transaction of the genesis block.
+    pub fn regtest() -> Self {
+        let genesis = "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206";
+        let mut mock = Self::default()
+            .with_blockchain_info(|info| {
+                info.chain = "regtest".into();
+                info.blocks = 0;
+                info.headers = 0;
+                info.best_block_hash = genesis.into();
+                info.difficulty = 4.656542373906925e-10;
+                info.time = 1_296_688_602;
+                info.median_time = 1_296_688_602;
+                info.verification_progress = 1.0;
+                info.initial_block_download = true;
+                info.chain_work = format!("{:064x}", 2);
+                info.size_on_disk = 293;
+            })
+            .with_mempool_info(|info| {
+                info.size = 0;
+                info.bytes = 0;
+                info.usage = 0;
+                info.total_fee = 0.0;
+                info.unbroadcast_count = 0;
+            })
+            .with_network_info(|info| {
+                info.connections = 0;
+                info.connections_in = 0;
+                info.connections_out = 0;
+                info.local_addresses.clear();
+            })
+            .with_peer_info(|peers| peers.0.clear())
+            .with_chain_tx_stats(|stats| {
+                *stats = ChainTxStats {
+                    time: 1_296_688_602,
+                    tx_count: 1,
+                    window_final_block_hash: genesis.into(),
+                    window_final_block_height: 0,
+                    window_block_count: 0,
+                    window_tx_count: None,
+                    window_interval: None,
+                    tx_rate: None,
+                }
+            })
+            .with_chain_tips(|tips| {
+                tips.0.truncate(1);
+                tips.0[0].height = 0;
+                tips.0[0].hash = genesis.into();
+            })
+            .with_chain_states(|states| {
+                states.headers = 0;
+                states.chain_states.truncate(1);
+                states.chain_states[0].blocks = 0;
+                states.chain_states[0].best_block_hash = genesis.into();
+                states.chain_states[0].verification_progress = 1.0;
+                states.chain_states[0].validated = true;
+            })
+            .with_raw_mempool(|mempool| mempool.0.clear())
+            .with_error("getblockstats", "Can't read undo data from disk")
+            .with_error(
+                "getrawtransaction",
+                "The genesis block coinbase is not considered an ordinary transaction and cannot be retrieved",
+            );
+        mock.block_txids.tx.truncate(1);
+        mock.block_headers =
+            BTreeMap::from([(genesis.to_string(), BlockHeader { time: 1_296_688_602, previous_block_hash: None })]);
+        for target in [2, 6, 12, 144] {
+            mock = mock.with_fee_rate(target, None);
+        }
+        mock
+    }
+
+    /// Adjusts the `getblockchaininfo` response.
+    pub fn with_blockchain_info(mut self, update: impl FnOnce(&mut GetBlockchainInfo)) -> Self {
+        update(&mut self.blockchain_info);
+        self
+    }
+
+    /// Adjusts the `getmempoolinfo` response.
+    pub fn with_mempool_info(mut self, update: impl FnOnce(&mut GetMempoolInfo)) -> Self {
+        update(&mut self.mempool_info);
+        self
+    }
+
+    /// Adjusts the `getnetworkinfo` response.
+    pub fn with_network_info(mut self, update: impl FnOnce(&mut GetNetworkInfo)) -> Self {
+        update(&mut self.network_info);
+        self
+    }
+
+    /// Adjusts the `getpeerinfo` response.
+    pub fn with_peer_info(mut self, update: impl FnOnce(&mut GetPeerInfo)) -> Self {
+        update(&mut self.peer_info);
+        self
+    }
+
+    /// Adjusts the `getmininginfo` response.
+    pub fn with_mining_info(mut self, update: impl FnOnce(&mut MiningInfo)) -> Self {
+        update(&mut self.mining_info);
+        self
+    }
+
+    /// Adjusts the `getchaintxstats` response.
+    pub fn with_chain_tx_stats(mut self, update: impl FnOnce(&mut ChainTxStats)) -> Self {
+        update(&mut self.chain_tx_stats);
+        self
+    }
+
+    /// Adjusts the `getnettotals` response.
+    pub fn with_net_totals(mut self, update: impl FnOnce(&mut GetNetTotals)) -> Self {
+        update(&mut self.net_totals);
+        self
+    }
+
+    /// Adjusts the `getchaintips` response.
+    pub fn with_chain_tips(mut self, update: impl FnOnce(&mut GetChainTips)) -> Self {
+        update(&mut self.chain_tips);
+        self
+    }
+
+    /// Adjusts the `getblockstats` response.
+    pub fn with_block_stats(mut self, update: impl FnOnce(&mut BlockStats)) -> Self {
+        update(&mut self.block_stats);
+        self
+    }
+
+    /// Adjusts the `getchainstates` response.
+    pub fn with_chain_states(mut self, update: impl FnOnce(&mut GetChainStates)) -> Self {
+        update(&mut self.chain_states);
+        self
+    }
+
+    /// Adjusts the `getrawmempool` response.
+    pub fn with_raw_mempool(mut self, update: impl FnOnce(&mut GetRawMempoolVerbose)) -> Self {
+        update(&mut self.raw_mempool);
+        self
+    }
+
+    /// Answers `getblockheader` for `hash` with `header`.
+    pub fn with_block_header(mut self, hash: &str, header: BlockHeader) -> Self {
+        self.block_headers.insert(hash.to_string(), header);
+        self
+    }
+
+    /// Adjusts the tip's coinbase transaction returned by `getrawtransaction`.
+    pub fn with_coinbase(mut self, update: impl FnOnce(&mut RawTransaction)) -> Self {
+        update(&mut self.coinbase);
+        self
+    }
+
+    pub fn with_uptime(mut self, seconds: u32) -> Self {
+        self.uptime = seconds;
+        self
+    }
+
+    /// Sets the `estimatesmartfee` rate for `conf_target`; `None` reports insufficient data.
+    pub fn with_fee_rate(mut self, conf_target: u32, rate: Option<f64>) -> Self {
+        self.fee_rates.insert(conf_target, rate);
+        self
+    }
+
+    /// Makes `estimatesmartfee` for `conf_target` report `error` along with its rate, like a
+    /// node falling back to a less confident estimate.
+    pub fn with_fee_estimate_error(mut self, conf_target: u32, error: &str) -> Self {
+        self.fee_errors.insert(conf_target, vec![error.to_string()]);
+        self
+    }
+
+    /// Loads a wallet named `name`.
+    pub fn with_wallet(mut self, name: &str, info: WalletInfo) -> Self {
+        self.wallets.insert(name.to_string(), info);
+        self
+    }
+
+    /// Makes `scantxoutset` find `balance` BTC for `descriptor`.
+    pub fn with_descriptor_balance(mut self, descriptor: &str, balance: f64) -> Self {
+        self.descriptor_balances.insert(descriptor.to_string(), balance);
+        self
+    }
+
+    /// Sets the untyped result `call_json` returns for `method`; unset methods return `null`.
+    pub fn with_json_response(mut self, method: &str, response: serde_json::Value) -> Self {
+        self.json_responses.insert(method.to_string(), response);
+        self
+    }
+
+    /// Makes `method` (an RPC name such as `getmempoolinfo`) fail with `message`.
+    pub fn with_error(mut self, method: &str, message: &str) -> Self {
+        self.errors.insert(method.to_string(), message.to_string());
+        self
+    }
+
+    /// Makes every RPC fail while set, e.g. to simulate the node going away mid-test.
+    pub fn set_outage(&self, outage: bool) {
+        self.outage.store(outage, Ordering::SeqCst);
+    }
+
+    /// Makes every RPC fail with HTTP 401 while set, like after the node's password changed.
+    pub fn set_unauthorized(&self, unauthorized: bool) {
+        self.unauthorized.store(unauthorized, Ordering::SeqCst);
+    }
+
+    fn respond<T: Clone>(&self, method: &str, response: &T) -> Result<T, Error> {
+        if self.outage.load(Ordering::SeqCst) {
+            return Err(Error::Config("simulated outage".to_string()));
+        }
+        if self.unauthorized.load(Ordering::SeqCst) {
+            let status = TransportError::Status { status: 401, body: String::new() };
+            return Err(corepc_client::client_sync::Error::JsonRpc(jsonrpc::Error::Transport(Box::new(status))).into());
+        }
+        match self.errors.get(method) {
+            Some(message) => Err(Error::Config(message.clone())),
+            None => Ok(response.clone()),
+        }
+    }
+}
+
+impl NodeClient for MockNodeClient {
+    fn get_blockchain_info(&self) -> Result<GetBlockchainInfo, Error> {
+        self.respond("getblockchaininfo", &self.blockchain_info)
+    }
+
+    fn get_mempool_info(&self) -> Result<GetMempoolInfo, Error> {
+        self.respond("getmempoolinfo", &self.mempool_info)
+    }
+
+    fn get_network_info(&self) -> Result<GetNetworkInfo, Error> {
+        self.respond("getnetworkinfo", &self.network_info)
+    }
+
+    fn get_peer_info(&self) -> Result<GetPeerInfo, Error> {
+        self.respond("getpeerinfo", &self.peer_info)
+    }
+
+    fn get_mining_info(&self) -> Result<MiningInfo, Error> {
+        self.respond("getmininginfo", &self.mining_info)
+    }
+
+    fn get_chain_tx_stats(&self) -> Result<ChainTxStats, Error> {
+        self.respond("getchaintxstats", &self.chain_tx_stats)
+    }
+
+    fn get_net_totals(&self) -> Result<GetNetTotals, Error> {
+        self.respond("getnettotals", &self.net_totals)
+    }
+
+    fn estimate_smart_fee(&self, conf_target: u32) -> Result<EstimateSmartFee, Error> {
+        let fee_rate = self.fee_rates.get(&conf_target).copied().unwrap_or(Some(0.00010));
+        let errors = match fee_rate {
+            Some(_) => self.fee_errors.get(&conf_target).cloned(),
+            None => Some(vec!["Insufficient data or no feerate found".to_string()]),
+        };
+        self.respond(&format!("estimatesmartfee/{conf_target}"), &EstimateSmartFee { fee_rate, errors, blocks: conf_target })
+    }
+
+    fn get_chain_tips(&self) -> Result<GetChainTips, Error> {
+        self.respond("getchaintips", &self.chain_tips)
+    }
+
+    fn uptime(&self) -> Result<u32, Error> {
+        self.respond("uptime", &self.uptime)
+    }
+
+    fn get_block_stats_by_height(&self, _height: u32, stats: &[String]) -> Result<BlockStats, Error> {
+        // Like the node, answer with only the requested statistics
+        let serde_json::Value::Object(mut all) = serde_json::to_value(&self.block_stats).expect("serializable") else {
+            unreachable!("BlockStats serializes to an object");
+        };
+        all.retain(|name, _| stats.contains(name));
+        let filtered = serde_json::from_value(all.into()).expect("deserializable");
+        self.respond("getblockstats", &filtered)
+    }
+
+    fn get_chain_states(&self) -> Result<GetChainStates, Error> {
+        self.respond("getchainstates", &self.chain_states)
+    }
+
+    fn get_raw_mempool_verbose(&self) -> Result<GetRawMempoolVerbose, Error> {
+        self.respond("getrawmempool", &self.raw_mempool)
+    }
+
+    fn get_block_txids(&self, _block_hash: &str) -> Result<BlockTxids, Error> {
+        self.respond("getblock", &self.block_txids)
+    }
+
+    fn get_block_header(&self, block_hash: &str) -> Result<BlockHeader, Error> {
+        match self.block_headers.get(block_hash) {
+            Some(header) => self.respond("getblockheader", header),
+            None => Err(Error::Config("Block not found".to_string())),
+        }
+    }
+
+    /// Walks back from the tip through the `block_headers` chain.
+    fn get_block_hash(&self, height: i64) -> Result<String, Error> {
+        let mut hash = self.blockchain_info.best_block_hash.clone();
+        for _ in height..self.blockchain_info.blocks {
+            let parent = self.block_headers.get(&hash).and_then(|header| header.previous_block_hash.clone());
+            hash = parent.ok_or_else(|| Error::Config("Block height out of range".to_string()))?;
+        }
+        self.respond("getblockhash", &hash)
+    }
+
+    fn get_raw_transaction(&self, _txid: &str, _block_hash: &str) -> Result<RawTransaction, Error> {
+        self.respond("getrawtransaction", &self.coinbase)
+    }
+
+    fn get_wallet_info(&self, wallet: &str) -> Result<WalletInfo, Error> {
+        match self.wallets.get(wallet) {
+            Some(info) => self.respond(&format!("getwalletinfo/{wallet}"), info),
+            None => Err(Error::Config(format!("Requested wallet does not exist or is not loaded: {wallet}"))),
+        }
+    }
+
+    fn scan_tx_out_set(&self, descriptor: &str) -> Result<ScanTxOutSet, Error> {
+        match self.descriptor_balances.get(descriptor) {
+            Some(&total_amount) => self.respond("scantxoutset", &ScanTxOutSet { success: true, total_amount }),
+            None => Err(Error::Config(format!("'{descriptor}' is not a valid descriptor function"))),
+        }
+    }
+
+    fn call_json(&self, method: &str, _params: &[serde_json::Value]) -> Result<serde_json::Value, Error> {
+        let response = self.json_responses.get(method).cloned().unwrap_or_default();
+        self.respond(method, &response)
+    }
+}
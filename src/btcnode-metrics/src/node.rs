@@ -1,12 +1,22 @@
-use corepc_client::client_sync::{v28::Client, Auth};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use corepc_client::client_sync::Error as RpcError;
 use corepc_client::types::v28::{
-    EstimateSmartFee, GetBlockStats, GetBlockchainInfo, GetChainTips, GetMempoolInfo, GetNetTotals,
-    GetNetworkInfo, GetPeerInfo,
+    EstimateSmartFee, GetBlockchainInfo, GetChainStates, GetChainTips, GetMempoolInfo, GetNetTotals,
+    GetNetworkInfo, GetPeerInfo, GetRawMempoolVerbose,
 };
-use serde::Deserialize;
+use jsonrpc::client::Transport;
+use prometheus::{IntCounterVec, Opts, Registry};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use tracing::warn;
 
 use crate::Error;
 use crate::config::NodeConfig;
+use crate::transport::{ConnectionCounters, DEFAULT_USER_AGENT, HttpTransport};
 
 /// Custom type for `getmininginfo` that fixes `network_hash_ps` from `i64` to `f64`.
 ///
@@ -48,6 +58,176 @@ pub struct ChainTxStats {
     pub tx_rate: Option<f64>,
 }
 
+/// Bitcoin Core versions, as `getnetworkinfo` reports them (e.g. 280100 for 28.1.0), whose
+/// RPC results match the v28 types parsed here. Others may fail on individual fields.
+pub const SUPPORTED_CORE_VERSIONS: RangeInclusive<u64> = 280000..=299999;
+
+/// A `getnetworkinfo` version the way Core prints it, e.g. `28.1.0` for 280100.
+pub fn core_version_string(version: u64) -> String {
+    format!("{}.{}.{}", version / 10000, version / 100 % 100, version % 100)
+}
+
+/// `getblockstats` statistics that feed the latest-block gauges, by their RPC names.
+pub const BLOCK_STATS_FIELDS: [&str; 27] = [
+    "txs",
+    "total_size",
+    "total_weight",
+    "avgfee",
+    "avgfeerate",
+    "medianfee",
+    "minfee",
+    "maxfee",
+    "minfeerate",
+    "maxfeerate",
+    "totalfee",
+    "subsidy",
+    "ins",
+    "outs",
+    "swtxs",
+    "swtotal_size",
+    "swtotal_weight",
+    "total_out",
+    "utxo_increase",
+    "utxo_size_inc",
+    "utxo_increase_actual",
+    "utxo_size_inc_actual",
+    "avgtxsize",
+    "mediantxsize",
+    "maxtxsize",
+    "mintxsize",
+    "feerate_percentiles",
+];
+
+/// Custom type for `getblockstats` with every statistic optional.
+///
+/// The node only returns the statistics named in the request's filter, which the
+/// upstream `GetBlockStats` (all fields required) can't deserialize.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BlockStats {
+    pub txs: Option<i64>,
+    pub total_size: Option<i64>,
+    pub total_weight: Option<u64>,
+    #[serde(rename = "avgfee")]
+    pub average_fee: Option<u64>,
+    #[serde(rename = "avgfeerate")]
+    pub average_fee_rate: Option<u64>,
+    #[serde(rename = "medianfee")]
+    pub median_fee: Option<u64>,
+    #[serde(rename = "minfee")]
+    pub minimum_fee: Option<u64>,
+    #[serde(rename = "maxfee")]
+    pub max_fee: Option<u64>,
+    #[serde(rename = "minfeerate")]
+    pub minimum_fee_rate: Option<u64>,
+    #[serde(rename = "maxfeerate")]
+    pub max_fee_rate: Option<u64>,
+    #[serde(rename = "totalfee")]
+    pub total_fee: Option<u64>,
+    pub subsidy: Option<u64>,
+    #[serde(rename = "ins")]
+    pub inputs: Option<i64>,
+    #[serde(rename = "outs")]
+    pub outputs: Option<i64>,
+    #[serde(rename = "swtxs")]
+    pub segwit_txs: Option<i64>,
+    #[serde(rename = "swtotal_size")]
+    pub segwit_total_size: Option<i64>,
+    #[serde(rename = "swtotal_weight")]
+    pub segwit_total_weight: Option<u64>,
+    pub total_out: Option<u64>,
+    pub utxo_increase: Option<i32>,
+    #[serde(rename = "utxo_size_inc")]
+    pub utxo_size_increase: Option<i64>,
+    /// Like `utxo_increase`, but not counting unspendable outputs.
+    pub utxo_increase_actual: Option<i32>,
+    #[serde(rename = "utxo_size_inc_actual")]
+    pub utxo_size_increase_actual: Option<i64>,
+    #[serde(rename = "avgtxsize")]
+    pub average_tx_size: Option<i64>,
+    #[serde(rename = "mediantxsize")]
+    pub median_tx_size: Option<i64>,
+    #[serde(rename = "maxtxsize")]
+    pub max_tx_size: Option<i64>,
+    #[serde(rename = "mintxsize")]
+    pub minimum_tx_size: Option<i64>,
+    #[serde(rename = "feerate_percentiles")]
+    pub fee_rate_percentiles: Option<[u64; 5]>,
+}
+
+/// The part of a `getblock` (verbosity 1) response needed to find the coinbase.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlockTxids {
+    pub tx: Vec<String>,
+}
+
+/// The parts of a verbose `getblockheader` response used for the block interval.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlockHeader {
+    pub time: i64,
+    /// Absent only for the genesis block.
+    #[serde(rename = "previousblockhash")]
+    pub previous_block_hash: Option<String>,
+}
+
+/// The parts of a verbose `getrawtransaction` response used for coinbase tracking.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RawTransaction {
+    pub vin: Vec<RawTxInput>,
+    pub vout: Vec<RawTxOutput>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RawTxInput {
+    /// Hex scriptSig of a coinbase input; absent for any other input.
+    pub coinbase: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RawTxOutput {
+    /// Output value in BTC.
+    pub value: f64,
+}
+
+/// RPCs that act on a wallet; [`NodeClient::call_json`] sends these to `default_wallet`.
+const WALLET_RPCS: [&str; 16] = [
+    "getbalance",
+    "getbalances",
+    "getunconfirmedbalance",
+    "getwalletinfo",
+    "getreceivedbyaddress",
+    "getreceivedbylabel",
+    "gettransaction",
+    "getaddressinfo",
+    "getaddressesbylabel",
+    "listunspent",
+    "listtransactions",
+    "listsinceblock",
+    "listlockunspent",
+    "listreceivedbyaddress",
+    "listreceivedbylabel",
+    "listlabels",
+];
+
+/// The parts of a `getwalletinfo` response that are exported, in BTC. Newer Core
+/// versions drop the balance fields in favor of `getbalances`, so they're optional.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WalletInfo {
+    pub balance: Option<f64>,
+    pub unconfirmed_balance: Option<f64>,
+    pub immature_balance: Option<f64>,
+    #[serde(rename = "txcount")]
+    pub tx_count: u64,
+}
+
+/// The parts of a `scantxoutset start` response that are exported.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScanTxOutSet {
+    /// False when the scan was aborted (`scantxoutset abort`).
+    pub success: bool,
+    /// Sum of the unspent outputs matching the descriptors, in BTC.
+    pub total_amount: f64,
+}
+
 pub trait NodeClient: Send + Sync {
     fn get_blockchain_info(&self) -> Result<GetBlockchainInfo, Error>;
     fn get_mempool_info(&self) -> Result<GetMempoolInfo, Error>;
@@ -59,68 +239,526 @@ pub trait NodeClient: Send + Sync {
     fn estimate_smart_fee(&self, conf_target: u32) -> Result<EstimateSmartFee, Error>;
     fn get_chain_tips(&self) -> Result<GetChainTips, Error>;
     fn uptime(&self) -> Result<u32, Error>;
-    fn get_block_stats_by_height(&self, height: u32) -> Result<GetBlockStats, Error>;
+    /// Fetches only the named statistics (see [`BLOCK_STATS_FIELDS`]) of the block at `height`.
+    fn get_block_stats_by_height(&self, height: u32, stats: &[String]) -> Result<BlockStats, Error>;
+    fn get_chain_states(&self) -> Result<GetChainStates, Error>;
+    fn get_raw_mempool_verbose(&self) -> Result<GetRawMempoolVerbose, Error>;
+    fn get_block_txids(&self, block_hash: &str) -> Result<BlockTxids, Error>;
+    fn get_block_header(&self, block_hash: &str) -> Result<BlockHeader, Error>;
+    /// Hash of the block at `height` in the active chain.
+    fn get_block_hash(&self, height: i64) -> Result<String, Error>;
+    /// Looks the transaction up in `block_hash`, so no `-txindex` is needed.
+    fn get_raw_transaction(&self, txid: &str, block_hash: &str) -> Result<RawTransaction, Error>;
+    /// `getwalletinfo` sent to the `/wallet/<name>` endpoint of one of the node's `wallets`.
+    fn get_wallet_info(&self, wallet: &str) -> Result<WalletInfo, Error>;
+    /// Scans the whole UTXO set for outputs matching `descriptor`; takes minutes on mainnet, so
+    /// it waits up to the node's `descriptor_scan_timeout_seconds` for the result.
+    fn scan_tx_out_set(&self, descriptor: &str) -> Result<ScanTxOutSet, Error>;
+    /// Calls any RPC and returns its untyped result, for `[[extra_metric]]` entries.
+    fn call_json(&self, method: &str, params: &[serde_json::Value]) -> Result<serde_json::Value, Error>;
 }
 
 pub struct BitcoinNode {
-    client: Client,
+    client: jsonrpc::Client,
+    /// Like `client` with the longer read timeout of `scantxoutset`; `client` itself when unset.
+    scan_client: Option<jsonrpc::Client>,
+    /// One client per configured wallet, since wallet RPCs are routed by URL path.
+    wallets: BTreeMap<String, jsonrpc::Client>,
+    /// Wallet that wallet RPCs made through `call_json` are routed to.
+    default_wallet: Option<String>,
+    counters: ConnectionCounters,
+    deserialize_errors: IntCounterVec,
+    limit: RpcLimit,
+}
+
+/// Caps the RPC calls in flight to one node; a blocking counterpart of
+/// `tokio::sync::Semaphore` for the sync client.
+struct RpcLimit {
+    max: usize,
+    in_flight: Mutex<usize>,
+    released: Condvar,
+}
+
+impl RpcLimit {
+    fn new(max: usize) -> Self {
+        Self { max: max.max(1), in_flight: Mutex::new(0), released: Condvar::new() }
+    }
+
+    /// Waits for a free slot, which is held until the returned permit is dropped.
+    fn acquire(&self) -> RpcPermit<'_> {
+        let mut in_flight = self.in_flight.lock().expect("RPC limit lock poisoned");
+        while *in_flight >= self.max {
+            in_flight = self.released.wait(in_flight).expect("RPC limit lock poisoned");
+        }
+        *in_flight += 1;
+        RpcPermit(self)
+    }
+}
+
+struct RpcPermit<'a>(&'a RpcLimit);
+
+impl Drop for RpcPermit<'_> {
+    fn drop(&mut self) {
+        *self.0.in_flight.lock().expect("RPC limit lock poisoned") -= 1;
+        self.0.released.notify_one();
+    }
 }
 
 impl BitcoinNode {
     pub fn new(config: &NodeConfig) -> Result<Self, Error> {
-        let auth = Auth::UserPass(config.rpc_user.clone(), config.rpc_password.clone());
-        let client = Client::new_with_auth(&config.rpc_url, auth)
-            .map_err(|e| Error::Config(format!("failed to create RPC client: {e}")))?;
-        Ok(Self { client })
+        if config.rpc_user.is_empty() {
+            return Err(Error::Config("failed to create RPC client: missing rpc_user".to_string()));
+        }
+        let user_agent = config.rpc_user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+        let labels = crate::metrics::node_labels(config.alias.as_deref(), config.instance_label()?.as_deref());
+        let counters = ConnectionCounters::new(labels.clone())?;
+        let connect_timeout = Duration::from_secs(config.rpc_connect_timeout_seconds);
+        let read_timeout = Duration::from_secs(config.rpc_read_timeout_seconds);
+        let transport = HttpTransport::new(
+            &config.rpc_url,
+            &config.rpc_user,
+            &config.rpc_password,
+            user_agent,
+            counters.clone(),
+        )
+        .map_err(|e| Error::Config(format!("failed to create RPC client: {e}")))?
+        .with_timeouts(connect_timeout, read_timeout);
+        let mut node = Self::with_transport(transport, counters, labels)?;
+        node.limit = RpcLimit::new(config.rpc_max_concurrency);
+        if !config.watch_descriptors.is_empty() {
+            let scan_timeout = Duration::from_secs(config.descriptor_scan_timeout_seconds);
+            let transport = HttpTransport::new(
+                &config.rpc_url,
+                &config.rpc_user,
+                &config.rpc_password,
+                user_agent,
+                node.counters.clone(),
+            )
+            .map_err(|e| Error::Config(format!("failed to create RPC client: {e}")))?
+            .with_timeouts(connect_timeout, scan_timeout);
+            node.scan_client = Some(jsonrpc::Client::with_transport(transport));
+        }
+        node.default_wallet = config.default_wallet.clone();
+        for wallet in &config.all_wallets() {
+            let url = format!("{}/wallet/{}", config.rpc_url.trim_end_matches('/'), encode_path_segment(wallet));
+            let transport =
+                HttpTransport::new(&url, &config.rpc_user, &config.rpc_password, user_agent, node.counters.clone())
+                    .map_err(|e| Error::Config(format!("failed to create RPC client for wallet {wallet}: {e}")))?
+                    .with_timeouts(connect_timeout, read_timeout);
+            node.wallets.insert(wallet.clone(), jsonrpc::Client::with_transport(transport));
+        }
+        Ok(node)
+    }
+
+    fn with_transport(
+        transport: impl Transport,
+        counters: ConnectionCounters,
+        labels: HashMap<String, String>,
+    ) -> Result<Self, Error> {
+        let deserialize_errors = IntCounterVec::new(
+            Opts::new(
+                "bitcoin_collector_deserialize_errors_total",
+                "Number of RPC results that didn't match the expected type, e.g. after a Bitcoin Core upgrade",
+            )
+            .const_labels(labels),
+            &["method"],
+        )?;
+        Ok(Self {
+            client: jsonrpc::Client::with_transport(transport),
+            scan_client: None,
+            wallets: BTreeMap::new(),
+            default_wallet: None,
+            counters,
+            deserialize_errors,
+            limit: RpcLimit::new(4),
+        })
+    }
+
+    /// Registers the RPC connection counters with `registry`.
+    pub fn register_metrics(&self, registry: &Registry) -> Result<(), Error> {
+        registry.register(Box::new(self.counters.opened.clone()))?;
+        registry.register(Box::new(self.counters.reused.clone()))?;
+        registry.register(Box::new(self.deserialize_errors.clone()))?;
+        Ok(())
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, args: &[serde_json::Value]) -> Result<T, Error> {
+        self.call_on(&self.client, method, args)
+    }
+
+    fn call_on<T: DeserializeOwned>(
+        &self,
+        client: &jsonrpc::Client,
+        method: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T, Error> {
+        let params = serde_json::value::to_raw_value(args).map_err(RpcError::from)?;
+        let request = client.build_request(method, Some(&*params));
+        let permit = self.limit.acquire();
+        let response = client.send_request(request).map_err(RpcError::from)?;
+        drop(permit);
+        match response.result() {
+            Ok(result) => Ok(result),
+            // A field Core renamed or retyped; count it so one RPC's breakage is visible
+            // while the other sections keep being collected
+            Err(jsonrpc::Error::Json(e)) => {
+                warn!("Unexpected {method} result, possibly from an unsupported Bitcoin Core version: {e}");
+                self.deserialize_errors.with_label_values(&[method]).inc();
+                Err(RpcError::from(jsonrpc::Error::Json(e)).into())
+            }
+            Err(e) => Err(RpcError::from(e).into()),
+        }
     }
 }
 
 impl NodeClient for BitcoinNode {
     fn get_blockchain_info(&self) -> Result<GetBlockchainInfo, Error> {
-        Ok(self.client.get_blockchain_info()?)
+        self.call("getblockchaininfo", &[])
     }
 
     fn get_mempool_info(&self) -> Result<GetMempoolInfo, Error> {
-        Ok(self.client.get_mempool_info()?)
+        self.call("getmempoolinfo", &[])
     }
 
     fn get_network_info(&self) -> Result<GetNetworkInfo, Error> {
-        Ok(self.client.get_network_info()?)
+        self.call("getnetworkinfo", &[])
     }
 
     fn get_peer_info(&self) -> Result<GetPeerInfo, Error> {
-        Ok(self.client.get_peer_info()?)
+        self.call("getpeerinfo", &[])
     }
 
     fn get_mining_info(&self) -> Result<MiningInfo, Error> {
         // Bypass upstream GetMiningInfo (which declares network_hash_ps as i64)
         // and deserialize directly into our corrected MiningInfo type.
-        Ok(self.client.call::<MiningInfo>("getmininginfo", &[])?)
+        self.call("getmininginfo", &[])
     }
 
     fn get_chain_tx_stats(&self) -> Result<ChainTxStats, Error> {
         // Bypass upstream GetChainTxStats (which declares tx_rate as Option<i64>)
         // and deserialize directly into our corrected ChainTxStats type.
-        Ok(self.client.call::<ChainTxStats>("getchaintxstats", &[])?)
+        self.call("getchaintxstats", &[])
     }
 
     fn get_net_totals(&self) -> Result<GetNetTotals, Error> {
-        Ok(self.client.get_net_totals()?)
+        self.call("getnettotals", &[])
     }
 
     fn estimate_smart_fee(&self, conf_target: u32) -> Result<EstimateSmartFee, Error> {
-        Ok(self.client.estimate_smart_fee(conf_target)?)
+        self.call("estimatesmartfee", &[conf_target.into()])
     }
 
     fn get_chain_tips(&self) -> Result<GetChainTips, Error> {
-        Ok(self.client.get_chain_tips()?)
+        self.call("getchaintips", &[])
     }
 
     fn uptime(&self) -> Result<u32, Error> {
-        Ok(self.client.uptime()?)
+        self.call("uptime", &[])
+    }
+
+    fn get_block_stats_by_height(&self, height: u32, stats: &[String]) -> Result<BlockStats, Error> {
+        self.call("getblockstats", &[height.into(), stats.into()])
+    }
+
+    fn get_chain_states(&self) -> Result<GetChainStates, Error> {
+        self.call("getchainstates", &[])
+    }
+
+    fn get_raw_mempool_verbose(&self) -> Result<GetRawMempoolVerbose, Error> {
+        self.call("getrawmempool", &[true.into()])
+    }
+
+    fn get_block_txids(&self, block_hash: &str) -> Result<BlockTxids, Error> {
+        self.call("getblock", &[block_hash.into(), 1.into()])
+    }
+
+    fn get_block_header(&self, block_hash: &str) -> Result<BlockHeader, Error> {
+        self.call("getblockheader", &[block_hash.into(), true.into()])
+    }
+
+    fn get_block_hash(&self, height: i64) -> Result<String, Error> {
+        self.call("getblockhash", &[height.into()])
+    }
+
+    fn get_raw_transaction(&self, txid: &str, block_hash: &str) -> Result<RawTransaction, Error> {
+        self.call("getrawtransaction", &[txid.into(), true.into(), block_hash.into()])
+    }
+
+    fn get_wallet_info(&self, wallet: &str) -> Result<WalletInfo, Error> {
+        let client = self
+            .wallets
+            .get(wallet)
+            .ok_or_else(|| Error::Config(format!("wallet {wallet} is not in the node's wallets")))?;
+        self.call_on(client, "getwalletinfo", &[])
+    }
+
+    fn scan_tx_out_set(&self, descriptor: &str) -> Result<ScanTxOutSet, Error> {
+        let client = self.scan_client.as_ref().unwrap_or(&self.client);
+        self.call_on(client, "scantxoutset", &["start".into(), vec![descriptor].into()])
+    }
+
+    fn call_json(&self, method: &str, params: &[serde_json::Value]) -> Result<serde_json::Value, Error> {
+        match &self.default_wallet {
+            Some(wallet) if WALLET_RPCS.contains(&method) => self.call_on(&self.wallets[wallet], method, params),
+            _ => self.call(method, params),
+        }
+    }
+}
+
+/// Percent-encodes a wallet name for use as a URL path segment.
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    /// Answers every request with a fixed `result`.
+    struct FixedTransport(&'static str);
+
+    impl Transport for FixedTransport {
+        fn send_request(&self, request: jsonrpc::Request) -> Result<jsonrpc::Response, jsonrpc::Error> {
+            Ok(jsonrpc::Response {
+                result: Some(serde_json::value::RawValue::from_string(self.0.to_string()).unwrap()),
+                error: None,
+                id: request.id,
+                jsonrpc: Some("2.0".to_string()),
+            })
+        }
+
+        fn send_batch(&self, _requests: &[jsonrpc::Request]) -> Result<Vec<jsonrpc::Response>, jsonrpc::Error> {
+            Ok(Vec::new())
+        }
+
+        fn fmt_target(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "fixed")
+        }
+    }
+
+    #[test]
+    fn test_deserialize_error_is_counted() {
+        let counters = ConnectionCounters::new(HashMap::new()).unwrap();
+        let transport = FixedTransport(r#"{"blocks": "many"}"#);
+        let node = BitcoinNode::with_transport(transport, counters, HashMap::new()).unwrap();
+
+        let err = node.get_mining_info().unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::VersionMismatch);
+        assert!(node.get_chain_tx_stats().is_err());
+        assert!(node.get_chain_tx_stats().is_err());
+
+        assert_eq!(node.deserialize_errors.with_label_values(&["getmininginfo"]).get(), 1);
+        assert_eq!(node.deserialize_errors.with_label_values(&["getchaintxstats"]).get(), 2);
+        // The uptime RPC's integer result still deserializes
+        let counters = ConnectionCounters::new(HashMap::new()).unwrap();
+        let node = BitcoinNode::with_transport(FixedTransport("1234"), counters, HashMap::new()).unwrap();
+        assert_eq!(node.uptime().unwrap(), 1234);
+        assert_eq!(node.deserialize_errors.with_label_values(&["uptime"]).get(), 0);
+    }
+
+    /// Serves `requests` JSON-RPC requests, each on its own connection, answering with
+    /// `respond(method)`; the thread returns each request's method and target path.
+    fn serve_rpc(
+        requests: usize,
+        respond: fn(&str) -> serde_json::Value,
+    ) -> (std::net::SocketAddr, std::thread::JoinHandle<Vec<(String, String)>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut targets = Vec::new();
+            for stream in listener.incoming().take(requests) {
+                let mut conn = BufReader::new(stream.unwrap());
+                let (target, request) = read_request(&mut conn);
+                let method = request["method"].as_str().unwrap().to_string();
+                let result = respond(&method);
+                targets.push((method, target));
+                write_response(&mut conn, &request, result, false);
+            }
+            targets
+        });
+        (addr, server)
+    }
+
+    /// Reads one HTTP request, returning its target path and JSON-RPC body.
+    fn read_request(conn: &mut BufReader<std::net::TcpStream>) -> (String, serde_json::Value) {
+        let mut line = String::new();
+        conn.read_line(&mut line).unwrap();
+        let target = line.split_whitespace().nth(1).unwrap().to_string();
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            conn.read_line(&mut header).unwrap();
+            if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                content_length = value.trim().parse().unwrap();
+            }
+            if header.trim().is_empty() {
+                break;
+            }
+        }
+        let mut body = vec![0; content_length];
+        conn.read_exact(&mut body).unwrap();
+        (target, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn write_response(
+        conn: &mut BufReader<std::net::TcpStream>,
+        request: &serde_json::Value,
+        result: serde_json::Value,
+        keep_alive: bool,
+    ) {
+        let response = serde_json::json!({"result": result, "error": null, "id": request["id"]}).to_string();
+        let connection = if keep_alive { "keep-alive" } else { "close" };
+        write!(
+            conn.get_mut(),
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: {connection}\r\n\r\n{response}",
+            response.len()
+        )
+        .unwrap();
+    }
+
+    fn wallet_or_uptime(method: &str) -> serde_json::Value {
+        match method {
+            "getwalletinfo" => serde_json::json!({"balance": 1.0, "txcount": 3}),
+            _ => serde_json::json!(1234),
+        }
+    }
+
+    #[test]
+    fn test_rpc_url_path_prefix_is_kept() {
+        let (addr, server) = serve_rpc(2, wallet_or_uptime);
+        let config: NodeConfig = toml::from_str(&format!(
+            "rpc_url = \"http://{addr}/node1/\"\nrpc_user = \"user\"\nrpc_password = \"pass\"\nwallets = [\"hot\"]"
+        ))
+        .unwrap();
+        let node = BitcoinNode::new(&config).unwrap();
+        assert_eq!(node.uptime().unwrap(), 1234);
+        assert_eq!(node.get_wallet_info("hot").unwrap().tx_count, 3);
+
+        let targets: Vec<_> = server.join().unwrap().into_iter().map(|(_, target)| target).collect();
+        assert_eq!(targets, ["/node1/", "/node1/wallet/hot"]);
+    }
+
+    #[test]
+    fn test_default_wallet_routes_only_wallet_rpcs() {
+        let (addr, server) = serve_rpc(3, wallet_or_uptime);
+        let config: NodeConfig = toml::from_str(&format!(
+            "rpc_url = \"http://{addr}\"\nrpc_user = \"user\"\nrpc_password = \"pass\"\ndefault_wallet = \"main\""
+        ))
+        .unwrap();
+        assert_eq!(config.all_wallets(), ["main"]);
+        let node = BitcoinNode::new(&config).unwrap();
+        node.call_json("getwalletinfo", &[]).unwrap();
+        node.call_json("uptime", &[]).unwrap();
+        assert_eq!(node.get_wallet_info("main").unwrap().tx_count, 3);
+
+        let targets = server.join().unwrap();
+        assert_eq!(targets[0], ("getwalletinfo".to_string(), "/wallet/main".to_string()));
+        assert_eq!(targets[1], ("uptime".to_string(), "/".to_string()));
+        assert_eq!(targets[2], ("getwalletinfo".to_string(), "/wallet/main".to_string()));
+    }
+
+    #[test]
+    fn test_read_timeout_fails_a_silent_node() {
+        // Accepts the connection but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config: NodeConfig = toml::from_str(&format!(
+            "rpc_url = \"http://{addr}\"\nrpc_user = \"user\"\nrpc_password = \"pass\"\nrpc_read_timeout_seconds = 1"
+        ))
+        .unwrap();
+        assert_eq!(config.rpc_connect_timeout_seconds, 60);
+        let node = BitcoinNode::new(&config).unwrap();
+
+        let start = std::time::Instant::now();
+        let err = node.uptime().unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::Connection, "{err}");
+        assert!(start.elapsed() < Duration::from_secs(10));
+        drop(listener);
+    }
+
+    #[test]
+    fn test_scans_outlast_the_read_timeout() {
+        let (addr, server) = serve_rpc(1, |_| {
+            std::thread::sleep(Duration::from_secs(2));
+            serde_json::json!({"success": true, "total_amount": 0.5})
+        });
+        let config: NodeConfig = toml::from_str(&format!(
+            "rpc_url = \"http://{addr}\"\nrpc_user = \"user\"\nrpc_password = \"pass\"\nrpc_read_timeout_seconds = 1\n\
+             watch_descriptors = [{{ name = \"cold\", descriptor = \"addr(bc1q)\" }}]"
+        ))
+        .unwrap();
+        assert_eq!(config.descriptor_scan_timeout_seconds, 3600);
+        let node = BitcoinNode::new(&config).unwrap();
+
+        assert_eq!(node.scan_tx_out_set("addr(bc1q)").unwrap().total_amount, 0.5);
+        assert_eq!(server.join().unwrap()[0].0, "scantxoutset");
+    }
+
+    #[test]
+    fn test_only_closed_pooled_connections_are_retried() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            // Answers, then closes the pooled connection as an idle timeout would
+            let mut conn = BufReader::new(listener.accept().unwrap().0);
+            let (_, request) = read_request(&mut conn);
+            write_response(&mut conn, &request, serde_json::json!(1), true);
+            read_request(&mut conn);
+            drop(conn);
+            // The retried request, then one that never gets an answer
+            let mut conn = BufReader::new(listener.accept().unwrap().0);
+            let (_, request) = read_request(&mut conn);
+            write_response(&mut conn, &request, serde_json::json!(2), true);
+            read_request(&mut conn);
+            listener.set_nonblocking(true).unwrap();
+            (conn, listener)
+        });
+        let config: NodeConfig = toml::from_str(&format!(
+            "rpc_url = \"http://{addr}\"\nrpc_user = \"user\"\nrpc_password = \"pass\"\nrpc_read_timeout_seconds = 1"
+        ))
+        .unwrap();
+        let node = BitcoinNode::new(&config).unwrap();
+
+        assert_eq!(node.uptime().unwrap(), 1);
+        assert_eq!(node.uptime().unwrap(), 2);
+        // A read timeout isn't resent, since the node may still be running the call
+        let err = node.uptime().unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::Connection, "{err}");
+        let (_conn, listener) = server.join().unwrap();
+        assert!(listener.accept().is_err());
+    }
+
+    #[test]
+    fn test_encode_path_segment() {
+        assert_eq!(encode_path_segment("hot-wallet_1"), "hot-wallet_1");
+        assert_eq!(encode_path_segment("my wallet/2"), "my%20wallet%2F2");
     }
 
-    fn get_block_stats_by_height(&self, height: u32) -> Result<GetBlockStats, Error> {
-        Ok(self.client.get_block_stats_by_height(height)?)
+    #[test]
+    fn test_rpc_limit_caps_concurrent_calls() {
+        let limit = RpcLimit::new(2);
+        let peak = Mutex::new((0, 0));
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let _permit = limit.acquire();
+                    {
+                        let mut peak = peak.lock().unwrap();
+                        peak.0 += 1;
+                        peak.1 = peak.1.max(peak.0);
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    peak.lock().unwrap().0 -= 1;
+                });
+            }
+        });
+        assert_eq!(peak.into_inner().unwrap(), (0, 2));
     }
 }
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use prometheus::proto::{LabelPair, MetricFamily, MetricType};
+
+/// `Content-Type` of [`encode`]'s output.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Name of the histogram exemplars are attached to.
+pub const SCRAPE_DURATION_HISTOGRAM: &str = "bitcoin_collector_scrape_duration_histogram";
+
+/// A collection's duration with the `tracing` span it ran in, so a slow scrape can be
+/// followed to its trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exemplar {
+    pub span_id: u64,
+    pub value: f64,
+    /// Unix time of the observation, in seconds.
+    pub timestamp: f64,
+}
+
+/// Encodes `families` in the OpenMetrics text format, attaching to each bucket of the scrape
+/// duration histogram the exemplar that fell into it, looked up by the series' `node` label
+/// (`None` for an unlabeled node).
+pub fn encode(families: &[MetricFamily], exemplars: &HashMap<Option<String>, Vec<Exemplar>>) -> String {
+    let mut out = String::new();
+    for family in families {
+        let name = family.name();
+        let (family_name, kind) = match family.get_field_type() {
+            // OpenMetrics names the counter family without the suffix its sample carries
+            MetricType::COUNTER => (name.strip_suffix("_total").unwrap_or(name), "counter"),
+            MetricType::GAUGE => (name, "gauge"),
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+        let _ = writeln!(out, "# HELP {family_name} {}", escape(family.help()));
+        let _ = writeln!(out, "# TYPE {family_name} {kind}");
+        for metric in family.get_metric() {
+            let labels = metric.get_label();
+            match family.get_field_type() {
+                MetricType::COUNTER => {
+                    sample(&mut out, &format!("{family_name}_total"), labels, None, metric.get_counter().value())
+                }
+                MetricType::GAUGE => sample(&mut out, name, labels, None, metric.get_gauge().value()),
+                MetricType::UNTYPED => sample(&mut out, name, labels, None, metric.untyped.value()),
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let node = labels.iter().find(|label| label.name() == "node").map(|label| label.value().to_string());
+                    let exemplars = match name {
+                        SCRAPE_DURATION_HISTOGRAM => exemplars.get(&node).map(Vec::as_slice).unwrap_or_default(),
+                        _ => &[],
+                    };
+                    let bucket_name = format!("{name}_bucket");
+                    let mut lower = f64::NEG_INFINITY;
+                    let bounds = histogram.get_bucket().iter().map(|b| (b.upper_bound(), b.cumulative_count()));
+                    let count = histogram.sample_count();
+                    for (upper, cumulative) in bounds.chain([(f64::INFINITY, count)]) {
+                        if upper <= lower {
+                            continue;
+                        }
+                        let le = ("le", number(upper));
+                        let exemplar = exemplars.iter().rev().find(|e| e.value > lower && e.value <= upper);
+                        let _ = write!(out, "{bucket_name}{} {cumulative}", label_set(labels, Some(le)));
+                        if let Some(exemplar) = exemplar {
+                            let _ = write!(
+                                out,
+                                " # {{span_id=\"{:016x}\"}} {} {}",
+                                exemplar.span_id,
+                                number(exemplar.value),
+                                number(exemplar.timestamp)
+                            );
+                        }
+                        out.push('\n');
+                        lower = upper;
+                    }
+                    sample(&mut out, &format!("{name}_count"), labels, None, count as f64);
+                    sample(&mut out, &format!("{name}_sum"), labels, None, histogram.sample_sum());
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        let q = ("quantile", number(quantile.quantile()));
+                        sample(&mut out, name, labels, Some(q), quantile.value());
+                    }
+                    sample(&mut out, &format!("{name}_count"), labels, None, summary.sample_count() as f64);
+                    sample(&mut out, &format!("{name}_sum"), labels, None, summary.sample_sum());
+                }
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn sample(out: &mut String, name: &str, labels: &[LabelPair], extra: Option<(&str, String)>, value: f64) {
+    let _ = writeln!(out, "{name}{} {}", label_set(labels, extra), number(value));
+}
+
+fn label_set(labels: &[LabelPair], extra: Option<(&str, String)>) -> String {
+    let mut pairs: Vec<String> = labels.iter().map(|l| format!("{}=\"{}\"", l.name(), escape(l.value()))).collect();
+    if let Some((name, value)) = extra {
+        pairs.push(format!("{name}=\"{value}\""));
+    }
+    if pairs.is_empty() { String::new() } else { format!("{{{}}}", pairs.join(",")) }
+}
+
+fn number(value: f64) -> String {
+    match value {
+        f64::INFINITY => "+Inf".to_string(),
+        f64::NEG_INFINITY => "-Inf".to_string(),
+        _ if value.is_nan() => "NaN".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', r"\\").replace('\n', r"\n").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Histogram, HistogramOpts, IntCounter, Registry};
+
+    #[test]
+    fn test_encode_with_exemplars() {
+        let registry = Registry::new();
+        let counter = IntCounter::new("bitcoin_test_errors_total", "Errors with \"quotes\"").unwrap();
+        let opts = HistogramOpts::new(SCRAPE_DURATION_HISTOGRAM, "Durations").buckets(vec![0.1, 1.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        counter.inc();
+        histogram.observe(0.5);
+        histogram.observe(2.0);
+
+        let exemplar = Exemplar { span_id: 42, value: 0.5, timestamp: 1_700_000_000.5 };
+        let text = encode(&registry.gather(), &HashMap::from([(None, vec![exemplar])]));
+
+        assert!(text.contains("# HELP bitcoin_test_errors Errors with \\\"quotes\\\"\n"), "{text}");
+        assert!(text.contains("# TYPE bitcoin_test_errors counter\nbitcoin_test_errors_total 1\n"), "{text}");
+        let buckets = format!(
+            "{SCRAPE_DURATION_HISTOGRAM}_bucket{{le=\"0.1\"}} 0\n\
+             {SCRAPE_DURATION_HISTOGRAM}_bucket{{le=\"1\"}} 1 # {{span_id=\"000000000000002a\"}} 0.5 1700000000.5\n\
+             {SCRAPE_DURATION_HISTOGRAM}_bucket{{le=\"+Inf\"}} 2\n\
+             {SCRAPE_DURATION_HISTOGRAM}_count 2\n"
+        );
+        assert!(text.contains(&buckets), "{text}");
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,411 @@
+use std::io::BufReader;
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prometheus::proto::{MetricFamily, MetricType};
+use prometheus::{IntCounter, Registry};
+
+use crate::Error;
+use crate::transport::{self, TransportError};
+
+const PUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Pushes gathered metric families to a Prometheus remote-write endpoint
+/// (e.g. Mimir or Thanos receive) as snappy-compressed protobuf.
+pub struct RemoteWriter {
+    host: String,
+    path: String,
+    conn: Mutex<Option<BufReader<TcpStream>>>,
+    failures: IntCounter,
+}
+
+impl RemoteWriter {
+    pub fn new(url: &str) -> Result<Self, Error> {
+        let (host, path) = transport::parse_http_url(url).map_err(|e| Error::Config(format!("remote_write: {e}")))?;
+        let failures = IntCounter::new(
+            "bitcoin_exporter_remote_write_failures_total",
+            "Number of remote-write pushes that failed",
+        )?;
+
+        Ok(Self { host, path, conn: Mutex::new(None), failures })
+    }
+
+    /// Registers the push failure counter.
+    pub fn register_metrics(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.failures.clone()))
+    }
+
+    /// Encodes `families` with the current time as the sample timestamp and POSTs them.
+    pub fn push(&self, families: &[MetricFamily]) -> Result<(), TransportError> {
+        let timestamp_ms =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or_default();
+        let body = snappy_compress(&encode_write_request(families, timestamp_ms));
+
+        let result = self.post(&body);
+        if result.is_err() {
+            self.failures.inc();
+        }
+        result
+    }
+
+    fn post(&self, body: &[u8]) -> Result<(), TransportError> {
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nContent-Type: application/x-protobuf\r\nContent-Encoding: snappy\r\nX-Prometheus-Remote-Write-Version: 0.1.0\r\nContent-Length: {}\r\nConnection: keep-alive\r\n\r\n",
+            self.path,
+            self.host,
+            transport::DEFAULT_USER_AGENT,
+            body.len(),
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+
+        let mut slot = self.conn.lock().expect("remote-write connection poisoned");
+        let reused = slot.take();
+        let (mut conn, (status, response, keep_alive)) = match reused {
+            Some(mut conn) => match transport::exchange(&mut conn, &request) {
+                Ok(exchanged) => (conn, exchanged),
+                // The receiver may have closed the idle connection; retry once on a fresh one
+                Err(e) if transport::closed_before_response(&e) => {
+                    let mut conn = transport::connect(&self.host, PUSH_TIMEOUT, PUSH_TIMEOUT)?;
+                    let exchanged = transport::exchange(&mut conn, &request)?;
+                    (conn, exchanged)
+                }
+                Err(e) => return Err(e),
+            },
+            None => {
+                let mut conn = transport::connect(&self.host, PUSH_TIMEOUT, PUSH_TIMEOUT)?;
+                let exchanged = transport::exchange(&mut conn, &request)?;
+                (conn, exchanged)
+            }
+        };
+
+        if keep_alive && conn.buffer().is_empty() {
+            *slot = Some(conn);
+        } else {
+            let _ = conn.get_mut().shutdown(std::net::Shutdown::Both);
+        }
+
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(TransportError::Status { status, body: String::from_utf8_lossy(&response).into_owned() })
+        }
+    }
+}
+
+/// Encodes metric families as a remote-write `WriteRequest` protobuf message.
+///
+/// Histograms and summaries are expanded into their `_bucket`/quantile, `_sum` and
+/// `_count` series the same way the text exposition format does.
+pub fn encode_write_request(families: &[MetricFamily], timestamp_ms: i64) -> Vec<u8> {
+    let mut out = Vec::new();
+    for family in families {
+        let name = family.name();
+        for metric in family.get_metric() {
+            let labels: Vec<(&str, &str)> = metric.get_label().iter().map(|l| (l.name(), l.value())).collect();
+            let mut series = |suffix: &str, extra: Option<(&str, String)>, value: f64| {
+                let metric_name = format!("{name}{suffix}");
+                let mut all: Vec<(&str, &str)> = labels.clone();
+                all.push(("__name__", &metric_name));
+                if let Some((label, ref label_value)) = extra {
+                    all.push((label, label_value));
+                }
+                put_bytes(&mut out, 1, &encode_time_series(&mut all, value, timestamp_ms));
+            };
+
+            match family.get_field_type() {
+                MetricType::COUNTER => series("", None, metric.counter.value()),
+                MetricType::GAUGE => series("", None, metric.gauge.value()),
+                MetricType::UNTYPED => series("", None, metric.untyped.value()),
+                MetricType::HISTOGRAM => {
+                    let histogram = &metric.histogram;
+                    for bucket in histogram.get_bucket() {
+                        let le = bucket.upper_bound().to_string();
+                        series("_bucket", Some(("le", le)), bucket.cumulative_count() as f64);
+                    }
+                    let count = histogram.sample_count() as f64;
+                    series("_bucket", Some(("le", "+Inf".to_string())), count);
+                    series("_sum", None, histogram.sample_sum());
+                    series("_count", None, count);
+                }
+                MetricType::SUMMARY => {
+                    let summary = &metric.summary;
+                    for quantile in summary.get_quantile() {
+                        let q = quantile.quantile().to_string();
+                        series("", Some(("quantile", q)), quantile.value());
+                    }
+                    series("_sum", None, summary.sample_sum());
+                    series("_count", None, summary.sample_count() as f64);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn encode_time_series(labels: &mut [(&str, &str)], value: f64, timestamp_ms: i64) -> Vec<u8> {
+    // Remote-write receivers require labels sorted by name
+    labels.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let mut series = Vec::new();
+    for (name, value) in labels.iter() {
+        let mut label = Vec::new();
+        put_bytes(&mut label, 1, name.as_bytes());
+        put_bytes(&mut label, 2, value.as_bytes());
+        put_bytes(&mut series, 1, &label);
+    }
+
+    let mut sample = Vec::new();
+    put_key(&mut sample, 1, 1);
+    sample.extend_from_slice(&value.to_le_bytes());
+    put_key(&mut sample, 2, 0);
+    put_varint(&mut sample, timestamp_ms as u64);
+    put_bytes(&mut series, 2, &sample);
+
+    series
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_key(out: &mut Vec<u8>, field: u32, wire_type: u8) {
+    put_varint(out, (u64::from(field) << 3) | u64::from(wire_type));
+}
+
+fn put_bytes(out: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    put_key(out, field, 2);
+    put_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Wraps `data` in a snappy block made only of literals.
+///
+/// Receivers only need a valid snappy block, not a well-compressed one; the payload
+/// is small enough that skipping back-references costs little.
+pub fn snappy_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 60_000 * 3 + 8);
+    put_varint(&mut out, data.len() as u64);
+    for chunk in data.chunks(65_536) {
+        let len = chunk.len() - 1;
+        if len < 60 {
+            out.push((len as u8) << 2);
+        } else if len < 256 {
+            out.push(60 << 2);
+            out.push(len as u8);
+        } else {
+            out.push(61 << 2);
+            out.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{GaugeVec, Histogram, HistogramOpts, Opts};
+
+    /// (labels, value, timestamp) of each series in a `WriteRequest`.
+    type Series = (Vec<(String, String)>, f64, i64);
+
+    fn read_varint(data: &[u8], pos: &mut usize) -> u64 {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = data[*pos];
+            *pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                break;
+            }
+        }
+        value
+    }
+
+    /// Fields of a protobuf message as (field, wire type, payload); varints are returned as
+    /// their LE bytes.
+    fn read_fields(data: &[u8]) -> Vec<(u64, u64, Vec<u8>)> {
+        let mut fields = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let key = read_varint(data, &mut pos);
+            let payload = match key & 7 {
+                0 => read_varint(data, &mut pos).to_le_bytes().to_vec(),
+                1 => {
+                    pos += 8;
+                    data[pos - 8..pos].to_vec()
+                }
+                2 => {
+                    let len = read_varint(data, &mut pos) as usize;
+                    pos += len;
+                    data[pos - len..pos].to_vec()
+                }
+                wire_type => panic!("unexpected wire type {wire_type}"),
+            };
+            fields.push((key >> 3, key & 7, payload));
+        }
+        fields
+    }
+
+    fn decode_write_request(data: &[u8]) -> Vec<Series> {
+        let string = |bytes: Vec<u8>| String::from_utf8(bytes).unwrap();
+        read_fields(data)
+            .into_iter()
+            .map(|(field, _, series)| {
+                assert_eq!(field, 1);
+                let (mut labels, mut value, mut timestamp) = (Vec::new(), f64::NAN, 0);
+                for (field, _, payload) in read_fields(&series) {
+                    if field == 1 {
+                        let mut label = read_fields(&payload).into_iter().map(|(_, _, bytes)| string(bytes));
+                        labels.push((label.next().unwrap(), label.next().unwrap()));
+                    } else {
+                        for (field, _, bytes) in read_fields(&payload) {
+                            match field {
+                                1 => value = f64::from_le_bytes(bytes.try_into().unwrap()),
+                                _ => timestamp = u64::from_le_bytes(bytes.try_into().unwrap()) as i64,
+                            }
+                        }
+                    }
+                }
+                (labels, value, timestamp)
+            })
+            .collect()
+    }
+
+    /// Decodes a snappy block, back-references included, as described in the format's
+    /// `format_description.txt`.
+    fn snappy_decompress(data: &[u8]) -> Vec<u8> {
+        let mut pos = 0;
+        let len = read_varint(data, &mut pos) as usize;
+        let mut out = Vec::with_capacity(len);
+        while pos < data.len() {
+            let tag = data[pos];
+            pos += 1;
+            let read_le = |pos: &mut usize, n: usize| {
+                let value = data[*pos..*pos + n].iter().rev().fold(0, |acc, &b| (acc << 8) | usize::from(b));
+                *pos += n;
+                value
+            };
+            let (length, offset) = match tag & 3 {
+                0 => {
+                    let length = match tag >> 2 {
+                        n @ 0..60 => usize::from(n),
+                        n => read_le(&mut pos, usize::from(n) - 59),
+                    } + 1;
+                    out.extend_from_slice(&data[pos..pos + length]);
+                    pos += length;
+                    continue;
+                }
+                1 => (usize::from((tag >> 2) & 7) + 4, (usize::from(tag >> 5) << 8) | read_le(&mut pos, 1)),
+                2 => (usize::from(tag >> 2) + 1, read_le(&mut pos, 2)),
+                _ => (usize::from(tag >> 2) + 1, read_le(&mut pos, 4)),
+            };
+            for _ in 0..length {
+                out.push(out[out.len() - offset]);
+            }
+        }
+        assert_eq!(out.len(), len, "length preamble doesn't match the decoded data");
+        out
+    }
+
+    #[test]
+    fn test_encode_gauge() {
+        let registry = Registry::new();
+        let gauge = GaugeVec::new(Opts::new("g", "Gauge"), &["z", "a"]).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        gauge.with_label_values(&["2", "1"]).set(1.5);
+
+        let encoded = encode_write_request(&registry.gather(), 300);
+
+        // Labels sorted by name, `__name__` first since '_' sorts before lowercase letters
+        let name_label = [&[0x0a, 8][..], b"__name__", &[0x12, 1], b"g"].concat();
+        let a_label = [&[0x0a, 1][..], b"a", &[0x12, 1], b"1"].concat();
+        let z_label = [&[0x0a, 1][..], b"z", &[0x12, 1], b"2"].concat();
+        // Value as a fixed64 double, timestamp as a varint
+        let sample = [&[0x09][..], &1.5f64.to_le_bytes(), &[0x10, 0xac, 0x02]].concat();
+        let series = [
+            &[0x0a, name_label.len() as u8][..],
+            &name_label,
+            &[0x0a, a_label.len() as u8],
+            &a_label,
+            &[0x0a, z_label.len() as u8],
+            &z_label,
+            &[0x12, sample.len() as u8],
+            &sample,
+        ]
+        .concat();
+        assert_eq!(encoded, [&[0x0a, series.len() as u8][..], &series].concat());
+    }
+
+    #[test]
+    fn test_encode_histogram() {
+        let registry = Registry::new();
+        let histogram = Histogram::with_opts(HistogramOpts::new("h", "Histogram").buckets(vec![0.5, 1.0])).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        histogram.observe(0.25);
+        histogram.observe(2.0);
+
+        let series = decode_write_request(&encode_write_request(&registry.gather(), 1_700_000_000_000));
+
+        let labels = |name: &str, le: Option<&str>| {
+            let mut labels = vec![("__name__".to_string(), name.to_string())];
+            labels.extend(le.map(|le| ("le".to_string(), le.to_string())));
+            labels
+        };
+        let expected = vec![
+            (labels("h_bucket", Some("0.5")), 1.0),
+            (labels("h_bucket", Some("1")), 1.0),
+            (labels("h_bucket", Some("+Inf")), 2.0),
+            (labels("h_sum", None), 2.25),
+            (labels("h_count", None), 2.0),
+        ];
+        let expected: Vec<Series> =
+            expected.into_iter().map(|(labels, value)| (labels, value, 1_700_000_000_000)).collect();
+        assert_eq!(series, expected);
+    }
+
+    #[test]
+    fn test_snappy_literal_tags() {
+        // Literal lengths up to 60 fit in the tag
+        assert_eq!(snappy_compress(b"abc"), [&[3, 2 << 2][..], b"abc"].concat());
+        let data = [7u8; 60];
+        assert_eq!(snappy_compress(&data), [&[60, 59 << 2][..], &data].concat());
+
+        // Up to 256 take one more byte
+        let data = [7u8; 61];
+        assert_eq!(snappy_compress(&data), [&[61, 60 << 2, 60][..], &data].concat());
+        let data = [7u8; 256];
+        assert_eq!(snappy_compress(&data), [&[0x80, 0x02, 60 << 2, 255][..], &data].concat());
+
+        // Longer ones two, little-endian
+        let data = [7u8; 300];
+        assert_eq!(snappy_compress(&data), [&[0xac, 0x02, 61 << 2, 0x2b, 0x01][..], &data].concat());
+
+        for len in [0, 1, 59, 60, 61, 255, 256, 257, 65_536] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            assert_eq!(snappy_decompress(&snappy_compress(&data)), data, "length {len}");
+        }
+    }
+
+    #[test]
+    fn test_snappy_splits_long_input() {
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = snappy_compress(&data);
+
+        // Length preamble, then 65,536-byte literals and the 18,928-byte remainder
+        let mut expected = vec![0xf0, 0x93, 0x09];
+        for chunk in data.chunks(65_536) {
+            expected.push(61 << 2);
+            expected.extend_from_slice(&((chunk.len() - 1) as u16).to_le_bytes());
+            expected.extend_from_slice(chunk);
+        }
+        assert_eq!(compressed, expected);
+        assert_eq!(snappy_decompress(&compressed), data);
+    }
+}
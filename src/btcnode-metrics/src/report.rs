@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::{Error, ErrorKind};
+
+/// Outcome of a single RPC call made during a collection.
+#[derive(Clone, Debug, Serialize)]
+pub struct RpcOutcome {
+    pub ok: bool,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub error_kind: Option<ErrorKind>,
+}
+
+/// Per-RPC results of one collection, keyed by RPC method.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct CollectReport {
+    pub rpcs: BTreeMap<String, RpcOutcome>,
+}
+
+impl CollectReport {
+    /// Runs `call`, recording its duration and result under `method`.
+    pub fn track<T>(&mut self, method: &str, call: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let start = Instant::now();
+        let result = call();
+        self.record(method, start.elapsed(), result.as_ref().err());
+        result
+    }
+
+    pub fn record(&mut self, method: &str, duration: Duration, error: Option<&Error>) {
+        self.rpcs.insert(
+            method.to_string(),
+            RpcOutcome {
+                ok: error.is_none(),
+                duration_ms: duration.as_secs_f64() * 1000.0,
+                error: error.map(|e| e.to_string()),
+                error_kind: error.map(Error::kind),
+            },
+        );
+    }
+
+    pub fn had_error(&self) -> bool {
+        self.rpcs.values().any(|outcome| !outcome.ok)
+    }
+
+    /// `Some(false)` when the node rejected the RPC credentials on any call, `Some(true)` when
+    /// it accepted them on one (including calls that then failed in the node), and `None` when
+    /// no call got that far, e.g. while the node is down.
+    pub fn auth_ok(&self) -> Option<bool> {
+        let kinds = || self.rpcs.values().map(|outcome| outcome.error_kind);
+        if kinds().any(|kind| kind == Some(ErrorKind::Auth)) {
+            return Some(false);
+        }
+        kinds().any(|kind| matches!(kind, None | Some(ErrorKind::Rpc | ErrorKind::VersionMismatch))).then_some(true)
+    }
+}
+
+/// One node's collection as kept in [`crate::MetricsService::history`].
+#[derive(Clone, Debug, Serialize)]
+pub struct CollectRecord {
+    /// Unix time the collection started, in seconds.
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    pub duration_ms: f64,
+    /// Whether the collection outlived the scrape timeout; its RPCs are then unknown.
+    pub timed_out: bool,
+    pub rpcs: CollectReport,
+}
+
+/// Quick node status served by the JSON health endpoint.
+#[derive(Clone, Debug, Serialize)]
+pub struct NodeStatus {
+    /// `ok`, `syncing` (initial block download) or `unreachable`.
+    pub status: &'static str,
+    pub node_reachable: bool,
+    pub blocks: Option<i64>,
+    pub verification_progress: Option<f64>,
+    pub ibd: Option<bool>,
+    pub uptime_seconds: Option<u32>,
+}
+
+impl NodeStatus {
+    pub fn new(blockchain: Option<(i64, f64, bool)>, uptime_seconds: Option<u32>) -> Self {
+        let status = match blockchain {
+            None => "unreachable",
+            Some((_, _, true)) => "syncing",
+            Some((_, _, false)) => "ok",
+        };
+        Self {
+            status,
+            node_reachable: blockchain.is_some(),
+            blocks: blockchain.map(|(blocks, _, _)| blocks),
+            verification_progress: blockchain.map(|(_, progress, _)| progress),
+            ibd: blockchain.map(|(_, _, ibd)| ibd),
+            uptime_seconds,
+        }
+    }
+}
@@ -1,23 +1,292 @@
-use crate::{MetricsCollector, NodeClient};
-use prometheus::Encoder;
-use prometheus::TextEncoder;
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::report::{CollectRecord, NodeStatus};
+use crate::{CollectReport, CollectorConfig, Error, MetricsCollector, NodeClient};
+use prometheus::proto::{MetricFamily, MetricType};
+use prometheus::{Gauge, Registry, TextEncoder};
+use tracing::{Dispatch, warn};
+
+/// One node's collector plus a collection of it that outlived the scrape timeout;
+/// later scrapes wait on that instead of piling up new collections behind a stuck node.
+struct NodeCollector<N: NodeClient> {
+    collector: Arc<MetricsCollector<N>>,
+    in_flight: Mutex<Option<Receiver<CollectReport>>>,
+}
 
 pub struct MetricsService<N: NodeClient> {
-    collector: MetricsCollector<N>,
+    nodes: Vec<NodeCollector<N>>,
+    registry: Registry,
+    /// Whether collections run from a background loop (see [`MetricsService::refresh`])
+    /// rather than per scrape.
+    background: bool,
+    /// Kept between scrapes so encoding reuses its capacity instead of regrowing it.
+    buffer: Mutex<String>,
+    /// `bitcoin_exporter_series_count`, set from each gather's own output.
+    series_count: Gauge,
+    /// The latest `report_history_size` collections, oldest first.
+    history: Mutex<VecDeque<CollectRecord>>,
+}
+
+const SERIES_COUNT_NAME: &str = "bitcoin_exporter_series_count";
+
+/// Number of series the text format exposes for `families`: one per gauge or counter
+/// sample, and for a histogram or summary one per bucket (`+Inf` included) or quantile
+/// plus its `_sum` and `_count`.
+fn series_count(families: &[MetricFamily]) -> usize {
+    families
+        .iter()
+        .flat_map(|family| family.get_metric().iter().map(move |metric| (family.get_field_type(), metric)))
+        .map(|(kind, metric)| match kind {
+            MetricType::HISTOGRAM => metric.get_histogram().get_bucket().len() + 3,
+            MetricType::SUMMARY => metric.get_summary().get_quantile().len() + 2,
+            _ => 1,
+        })
+        .sum()
 }
 
-impl<N: NodeClient> MetricsService<N> {
+impl<N: NodeClient + 'static> MetricsService<N> {
     pub fn new(collector: MetricsCollector<N>) -> Self {
-        Self { collector }
+        Self::with_collectors(vec![collector])
+    }
+
+    /// Serves several nodes' collectors, which must share one registry
+    /// (see [`crate::BitcoinMetricsBuilder::registry`]).
+    pub fn with_collectors(collectors: Vec<MetricsCollector<N>>) -> Self {
+        let first = collectors.first().expect("at least one collector");
+        let registry = first.metrics().registry.clone();
+        let background = first.config().refresh_interval_seconds.is_some();
+        let nodes = collectors
+            .into_iter()
+            .map(|collector| NodeCollector { collector: Arc::new(collector), in_flight: Mutex::new(None) })
+            .collect();
+        let help = "Number of time series in the exporter's latest /metrics output";
+        let series_count = Gauge::new(SERIES_COUNT_NAME, help).expect("valid metric name");
+        if let Err(e) = registry.register(Box::new(series_count.clone())) {
+            warn!("Failed to register {SERIES_COUNT_NAME}: {e}");
+        }
+        Self {
+            nodes,
+            registry,
+            background,
+            buffer: Mutex::new(String::new()),
+            series_count,
+            history: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Applies a reloaded collector config to every node: sections, thresholds and timeouts
+    /// take effect from the next collection. Whether collection runs in the background is
+    /// fixed at startup; only its interval follows the new config.
+    pub fn reload(&self, config: &CollectorConfig) -> Result<(), Error> {
+        for node in &self.nodes {
+            node.collector.metrics().set_sections(&config.enabled_sections().collect())?;
+            node.collector.set_config(config.clone());
+        }
+        Ok(())
+    }
+
+    /// Current `refresh_interval_seconds`, for the background refresh loop.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        self.nodes.first()?.collector.config().refresh_interval_seconds.map(Duration::from_secs)
+    }
+
+    /// Current `refresh_jitter_seconds`, zero when unset.
+    pub fn refresh_jitter(&self) -> Duration {
+        self.nodes
+            .first()
+            .and_then(|node| node.collector.config().refresh_jitter_seconds)
+            .map_or(Duration::ZERO, Duration::from_secs)
+    }
+
+    fn scrape_timeout(&self) -> Option<Duration> {
+        self.nodes.first()?.collector.config().scrape_timeout_seconds.map(Duration::from_secs)
+    }
+
+    /// Runs one collection of every node and returns the per-RPC report, with each
+    /// RPC keyed `<node>/<method>` when nodes are labeled.
+    pub fn collect(&self) -> CollectReport {
+        let mut merged = CollectReport::default();
+        for (node, report) in self.nodes.iter().zip(self.collect_all(None)) {
+            let Some(report) = report else { continue };
+            match &node.collector.metrics().node {
+                Some(alias) => merged
+                    .rpcs
+                    .extend(report.rpcs.into_iter().map(|(method, outcome)| (format!("{alias}/{method}"), outcome))),
+                None => merged.rpcs.extend(report.rpcs),
+            }
+        }
+        merged
+    }
+
+    /// How often to run [`MetricsService::scan_descriptors`]; `None` when no node watches any.
+    pub fn descriptor_scan_interval(&self) -> Option<Duration> {
+        let first = self.nodes.first()?;
+        let watching = self.nodes.iter().any(|node| !node.collector.watched_descriptors().is_empty());
+        watching.then(|| Duration::from_secs(first.collector.config().descriptor_scan_interval_seconds))
+    }
+
+    /// Looks up every node's watched descriptor balances; called by the descriptor scan loop.
+    pub fn scan_descriptors(&self) {
+        for node in &self.nodes {
+            node.collector.scan_descriptors();
+        }
+    }
+
+    /// Reports of the latest collections of every node, oldest first, for looking into
+    /// failures that have cleared by the time anyone checks the metrics.
+    pub fn history(&self) -> Vec<CollectRecord> {
+        self.history.lock().expect("history lock poisoned").iter().cloned().collect()
+    }
+
+    /// Cached status of each node for health probes, with its alias;
+    /// see [`MetricsCollector::status`].
+    pub fn status(&self) -> Vec<(Option<String>, NodeStatus)> {
+        self.nodes
+            .iter()
+            .map(|node| (node.collector.metrics().node.clone(), node.collector.status()))
+            .collect()
+    }
+
+    /// Runs one collection and returns the resulting metric families; in background mode,
+    /// returns those of the latest [`MetricsService::refresh`] instead.
+    pub fn gather(&self) -> Vec<MetricFamily> {
+        self.gather_checked().0
+    }
+
+    /// Like [`MetricsService::gather`], also returning whether any node's collection had an
+    /// RPC error or timed out.
+    fn gather_checked(&self) -> (Vec<MetricFamily>, bool) {
+        let had_error = if self.background {
+            // Set by the latest refresh, timeouts included
+            self.nodes.iter().any(|node| node.collector.metrics().scrape_error.get() != 0.0)
+        } else {
+            let reports = self.collect_all(self.scrape_timeout());
+            reports.iter().any(|report| report.as_ref().is_none_or(CollectReport::had_error))
+        };
+        let mut families = self.registry.gather();
+        // The gathered copy of the series count still holds the previous scrape's value
+        let count = series_count(&families) as f64;
+        self.series_count.set(count);
+        if let Some(family) = families.iter_mut().find(|family| family.name() == SERIES_COUNT_NAME)
+            && let Some(metric) = family.mut_metric().first_mut()
+        {
+            let mut gauge = prometheus::proto::Gauge::default();
+            gauge.set_value(count);
+            metric.set_gauge(gauge);
+        }
+        (families, had_error)
+    }
+
+    /// Runs one collection of every node, bounded by the scrape timeout; called by the
+    /// background refresh loop.
+    pub fn refresh(&self) {
+        self.collect_all(self.scrape_timeout());
     }
 
     pub fn scrape(&self) -> String {
-        self.collector.collect();
+        self.scrape_checked().0
+    }
+
+    /// The scrape body and whether any node's collection behind it had an RPC error or
+    /// timed out, for answering such scrapes with an error status.
+    pub fn scrape_checked(&self) -> (String, bool) {
+        let (metric_families, had_error) = self.gather_checked();
+        let mut buffer = self.buffer.lock().expect("scrape buffer lock poisoned");
+        buffer.clear();
+        // Encodes straight into the string, so there's no UTF-8 validation pass afterwards
+        TextEncoder::new().encode_utf8(&metric_families, &mut buffer).expect("encoding metrics should not fail");
+        (buffer.clone(), had_error)
+    }
+
+    /// Like [`MetricsService::scrape_checked`], in the OpenMetrics text format (see
+    /// [`crate::openmetrics::CONTENT_TYPE`]) with the scrape duration histogram's exemplars.
+    pub fn scrape_openmetrics(&self) -> (String, bool) {
+        let (metric_families, had_error) = self.gather_checked();
+        let exemplars = self
+            .nodes
+            .iter()
+            .map(|node| (node.collector.metrics().node.clone(), node.collector.scrape_duration_exemplars()))
+            .collect();
+        (crate::openmetrics::encode(&metric_families, &exemplars), had_error)
+    }
+
+    /// Collects every node in parallel on worker threads. With a `timeout`, nodes that
+    /// haven't finished by then are left running and keep their previous values, so the
+    /// scrape is still answered when a node is too slow.
+    fn collect_all(&self, timeout: Option<Duration>) -> Vec<Option<CollectReport>> {
+        let start = Instant::now();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let deadline = timeout.map(|timeout| start + timeout);
+        let waiting: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut in_flight = node.in_flight.lock().expect("in-flight collection lock poisoned");
+                let rx = in_flight.take().unwrap_or_else(|| {
+                    let (tx, rx) = mpsc::channel();
+                    let collector = Arc::clone(&node.collector);
+                    // Collects in the caller's span, so exemplars can point to it
+                    let span = tracing::Span::current();
+                    let dispatch = tracing::dispatcher::get_default(Dispatch::clone);
+                    std::thread::spawn(move || {
+                        let collect = || span.in_scope(|| collector.collect());
+                        let _ = tx.send(tracing::dispatcher::with_default(&dispatch, collect));
+                    });
+                    rx
+                });
+                (in_flight, rx)
+            })
+            .collect();
+
+        let mut reports = Vec::with_capacity(waiting.len());
+        for (node, (mut in_flight, rx)) in self.nodes.iter().zip(waiting) {
+            let metrics = node.collector.metrics();
+            let result = match deadline {
+                Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let record = |timed_out, rpcs| CollectRecord {
+                timestamp,
+                node: metrics.node.clone(),
+                duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+                timed_out,
+                rpcs,
+            };
+            match result {
+                Ok(report) => {
+                    metrics.scrape_timed_out.set(0.0);
+                    self.record(record(false, report.clone()));
+                    reports.push(Some(report));
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let seconds = timeout.unwrap_or_default().as_secs();
+                    warn!(node = metrics.node.as_deref(), "Collection exceeded scrape timeout of {seconds}s");
+                    *in_flight = Some(rx);
+                    metrics.scrape_error.set(1.0);
+                    metrics.scrape_timed_out.set(1.0);
+                    self.record(record(true, CollectReport::default()));
+                    reports.push(None);
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    warn!(node = metrics.node.as_deref(), "Collection thread exited without a result");
+                    metrics.scrape_error.set(1.0);
+                    reports.push(None);
+                }
+            }
+        }
+        reports
+    }
 
-        let encoder = TextEncoder::new();
-        let metric_families = self.collector.metrics().registry.gather();
-        let mut buffer = Vec::new();
-        encoder.encode(&metric_families, &mut buffer).expect("encoding metrics should not fail");
-        String::from_utf8(buffer).expect("prometheus text format is valid UTF-8")
+    fn record(&self, record: CollectRecord) {
+        let size = self.nodes.first().map_or(0, |node| node.collector.config().report_history_size);
+        let mut history = self.history.lock().expect("history lock poisoned");
+        history.push_back(record);
+        // Also shrinks a history kept from before a reload lowered the size
+        while history.len() > size {
+            history.pop_front();
+        }
     }
 }
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use prometheus::GaugeVec;
+use prometheus::core::Collector;
+use serde::Serialize;
+
+use crate::metrics::BitcoinMetrics;
+
+/// Defines [`BitcoinMetricsSnapshot`] with one field per gauge of [`BitcoinMetrics`]:
+/// plain gauges as `f64`, label-less gauge vecs (only exported once set) as `Option<f64>`,
+/// and single-label gauge vecs as a map from label value to value.
+macro_rules! snapshot {
+    (
+        gauges: [$($gauge:ident),* $(,)?],
+        optional: [$($optional:ident),* $(,)?],
+        labeled: [$($labeled:ident),* $(,)?] $(,)?
+    ) => {
+        /// Current values of a node's metrics, for embedders that use them directly
+        /// instead of scraping; see [`crate::MetricsCollector::snapshot`].
+        #[derive(Clone, Debug, Default, PartialEq, Serialize)]
+        pub struct BitcoinMetricsSnapshot {
+            $(pub $gauge: f64,)*
+            $(pub $optional: Option<f64>,)*
+            $(pub $labeled: BTreeMap<String, f64>,)*
+            /// `[[extra_metric]]` gauges by name.
+            pub extra: BTreeMap<String, f64>,
+            /// Last error of each RPC method that hasn't succeeded since, by method.
+            pub last_errors: BTreeMap<String, String>,
+        }
+
+        impl BitcoinMetricsSnapshot {
+            pub fn of(metrics: &BitcoinMetrics) -> Self {
+                Self {
+                    $($gauge: metrics.$gauge.get(),)*
+                    $($optional: labeled_values(&metrics.$optional).into_values().next(),)*
+                    $($labeled: labeled_values(&metrics.$labeled),)*
+                    extra: metrics.extra.iter().map(|extra| (extra.config.name.clone(), extra.gauge.get())).collect(),
+                    last_errors: last_errors(&metrics.last_error_info),
+                }
+            }
+        }
+    };
+}
+
+snapshot! {
+    gauges: [
+        blocks, headers, headers_blocks_gap, difficulty, difficulty_log2, verification_progress, size_on_disk,
+        initial_block_download, node_synced, node_in_ibd, seconds_since_synced, chain_pruned, chain_work_log2,
+        best_block_timestamp, block_timestamp_lag_seconds,
+        mempool_transactions, mempool_bytes, mempool_usage, mempool_max_bytes, mempool_min_fee, mempool_total_fee,
+        mempool_total_fee_sat,
+        mempool_min_relay_tx_fee, mempool_min_fee_above_relay, mempool_min_fee_ratio, mempool_headroom_bytes,
+        mempool_eviction_imminent, mempool_incremental_relay_fee,
+        mempool_unbroadcast_count, mempool_full_rbf,
+        mempool_tx_vsize_p50, mempool_tx_vsize_p90, mempool_tx_vsize_p99, mempool_oldest_unbroadcast_seconds,
+        connections, connections_in, connections_out, network_active, accepting_inbound, local_addresses_count,
+        node_version, protocol_version, core_version_supported, time_offset, relay_fee, incremental_fee,
+        relay_fee_sat_per_vb, incremental_fee_sat_per_vb,
+        peer_count, peers_inbound, peers_outbound, outbound_block_relay_connections, peers_total_bytes_sent,
+        peers_total_bytes_received,
+        peers_avg_ping_seconds, peers_avg_min_ping_seconds, peers_worst_min_ping_seconds,
+        peers_avg_connection_age_seconds, peers_min_connection_age_seconds, peers_min_fee_filter,
+        peers_max_fee_filter, peers_max_synced_headers, peers_addresses_processed, peers_addresses_rate_limited,
+        blocks_behind_peers, peer_series_truncated,
+        network_hash_ps, mining_pooled_tx,
+        chain_tx_count, chain_tx_rate, chain_tx_window_block_count, chain_tx_window_tx_count, chain_tx_window_interval,
+        chain_tx_per_day,
+        net_total_bytes_received, net_total_bytes_sent,
+        fee_estimate_2_blocks, fee_estimate_6_blocks, fee_estimate_12_blocks, fee_estimate_144_blocks,
+        chain_tips_count, node_uptime_seconds, node_start_timestamp,
+        latest_block_height, latest_block_txs, latest_block_size, latest_block_weight, latest_block_avg_fee,
+        latest_block_avg_fee_rate,
+        latest_block_median_fee, latest_block_min_fee, latest_block_max_fee, latest_block_min_fee_rate,
+        latest_block_max_fee_rate, latest_block_total_fee, latest_block_subsidy, latest_block_inputs,
+        latest_block_outputs, latest_block_segwit_txs, latest_block_segwit_total_size,
+        latest_block_segwit_total_weight, latest_block_total_out, latest_block_utxo_increase,
+        latest_block_utxo_size_increase, latest_block_utxo_increase_actual, latest_block_utxo_size_increase_actual,
+        latest_block_avg_tx_size, latest_block_median_tx_size, latest_block_max_tx_size, latest_block_min_tx_size,
+        latest_block_fee_rate_10th, latest_block_fee_rate_25th, latest_block_fee_rate_50th,
+        latest_block_fee_rate_75th, latest_block_fee_rate_90th, latest_block_coinbase_value,
+        warnings_active,
+        scrape_duration_seconds, scrape_error, scrape_timed_out, seconds_since_last_scrape, scrapes_in_progress,
+    ],
+    optional: [
+        disk_full_estimate_seconds, chain_mismatch, last_block_interval_seconds, connections_saturation_ratio,
+        net_bytes_received_per_second, net_bytes_sent_per_second, fee_pressure_index,
+        peers_oldest_last_block_seconds, peers_on_limited_networks, node_rpc_auth_ok,
+    ],
+    labeled: [
+        best_block_info, network_reachable, network_proxy_configured, network_limited, peers_by_transport, peers_by_network,
+        peer_bytes_sent, peer_bytes_received,
+        fee_estimate_available, fee_estimate_fallback, fee_estimate_last_update, latest_block_pool, chainstate_blocks,
+        chainstate_validated, warning_info, wallet_balance, wallet_unconfirmed_balance, wallet_immature_balance,
+        wallet_tx_count, watched_descriptor_balance, section_stale,
+    ],
+}
+
+/// `method` to `error` label of every `bitcoin_collector_last_error_info` series.
+fn last_errors(gauge_vec: &GaugeVec) -> BTreeMap<String, String> {
+    gauge_vec
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .filter_map(|metric| {
+            let label = |name: &str| metric.get_label().iter().find(|label| label.name() == name).map(|l| l.value());
+            Some((label("method")?.to_string(), label("error")?.to_string()))
+        })
+        .collect()
+}
+
+/// Values of a gauge vec with at most one variable label, keyed by that label's value
+/// (empty for a label-less vec). The `node` and `instance` const labels are skipped.
+fn labeled_values(gauge_vec: &GaugeVec) -> BTreeMap<String, f64> {
+    gauge_vec
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .map(|metric| {
+            let label = metric.get_label().iter().map(|label| (label.name(), label.value()));
+            let label = label.filter(|(name, _)| !["node", "instance"].contains(name)).map(|(_, value)| value).next();
+            (label.unwrap_or_default().to_string(), metric.get_gauge().value())
+        })
+        .collect()
+}
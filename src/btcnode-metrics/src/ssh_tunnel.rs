@@ -0,0 +1,218 @@
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::Error;
+use crate::config::{NodeConfig, SshTunnelConfig};
+
+/// How long `ssh` may take to log in and open the forward.
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Free local ports tried when no `local_port` is configured: the port is only probed, so
+/// another process may take it before `ssh` listens on it.
+const PORT_ATTEMPTS: usize = 3;
+
+/// How often the supervisor checks that `ssh` still runs, and so the least time between restarts.
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A local forward to a node's RPC port through `ssh -L`, from its `ssh_tunnel`. A supervisor
+/// thread restarts `ssh` on the same local port when it exits, e.g. when the connection drops
+/// for longer than its keepalives allow; RPCs fail until it is back. The `ssh` process is
+/// killed when this is dropped.
+pub struct SshTunnel {
+    child: Arc<Mutex<Child>>,
+    local_addr: SocketAddr,
+    stop: Option<Sender<()>>,
+    supervisor: Option<JoinHandle<()>>,
+}
+
+impl SshTunnel {
+    /// Starts `ssh` for `node`'s `ssh_tunnel` and waits until the forward accepts
+    /// connections; `None` when the node has no `ssh_tunnel`.
+    pub fn open(node: &NodeConfig) -> Result<Option<Self>, Error> {
+        let Some(ssh) = &node.ssh_tunnel else {
+            return Ok(None);
+        };
+        let remote_addr = ssh.remote_addr.clone().unwrap_or_else(|| node.rpc_address());
+
+        let mut attempt = 1;
+        let (command, child, local_addr) = loop {
+            // Also fails early when a configured local_port is already taken
+            let local_addr = TcpListener::bind((Ipv4Addr::LOCALHOST, ssh.local_port.unwrap_or(0)))
+                .and_then(|listener| listener.local_addr())
+                .map_err(|e| Error::Config(format!("ssh_tunnel: no usable local port: {e}")))?;
+            let mut command = ssh_command(ssh, local_addr, &remote_addr);
+            let mut child =
+                command.spawn().map_err(|e| Error::Config(format!("ssh_tunnel: failed to run ssh: {e}")))?;
+            match wait_ready(&mut child, local_addr, &ssh.host, READY_TIMEOUT) {
+                Ok(()) => break (command, child, local_addr),
+                Err(e) => {
+                    let exited = matches!(child.try_wait(), Ok(Some(_)));
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    if !exited || ssh.local_port.is_some() || attempt == PORT_ATTEMPTS {
+                        return Err(e);
+                    }
+                    warn!("{e}; retrying on another local port");
+                    attempt += 1;
+                }
+            }
+        };
+        info!(host = %ssh.host, local_addr = %local_addr, remote_addr = %remote_addr, "Opened SSH tunnel");
+
+        let child = Arc::new(Mutex::new(child));
+        let (stop, stopped) = mpsc::channel();
+        let supervisor = {
+            let child = Arc::clone(&child);
+            let host = ssh.host.clone();
+            thread::spawn(move || supervise(&child, command, &host, &stopped))
+        };
+        Ok(Some(Self { child, local_addr, stop: Some(stop), supervisor: Some(supervisor) }))
+    }
+
+    /// Local end of the forward.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// `node` with its `rpc_url` pointing at the local end of the forward.
+    pub fn node_config(&self, node: &NodeConfig) -> NodeConfig {
+        node.with_rpc_address(&self.local_addr.to_string())
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        // Stop the supervisor first so it can't restart the killed ssh
+        drop(self.stop.take());
+        if let Some(supervisor) = self.supervisor.take() {
+            let _ = supervisor.join();
+        }
+        let mut child = self.child.lock().expect("ssh tunnel lock poisoned");
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// `ssh` forwarding `local_addr` to `remote_addr` through `ssh.host`.
+fn ssh_command(ssh: &SshTunnelConfig, local_addr: SocketAddr, remote_addr: &str) -> Command {
+    let mut command = Command::new("ssh");
+    // Fail instead of prompting for a password or host key, and exit when the forward
+    // can't be set up rather than staying connected without it
+    command
+        .args(["-N", "-o", "BatchMode=yes", "-o", "ExitOnForwardFailure=yes", "-o", "ServerAliveInterval=30"])
+        .arg("-L")
+        .arg(format!("{local_addr}:{remote_addr}"))
+        .arg("-p")
+        .arg(ssh.port.to_string());
+    if let Some(key_file) = &ssh.key_file {
+        command.arg("-i").arg(key_file).args(["-o", "IdentitiesOnly=yes"]);
+    }
+    if let Some(user) = &ssh.user {
+        command.arg("-l").arg(user);
+    }
+    command.arg(&ssh.host).stdin(Stdio::null()).stdout(Stdio::null());
+    command
+}
+
+fn wait_ready(child: &mut Child, local_addr: SocketAddr, host: &str, timeout: Duration) -> Result<(), Error> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        // ssh has printed why to stderr
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(Error::Config(format!("ssh_tunnel: ssh to {host} exited with {status}")));
+        }
+        // ssh only listens once logged in
+        if TcpStream::connect_timeout(&local_addr, Duration::from_secs(1)).is_ok() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(Error::Config(format!(
+                "ssh_tunnel: forward through {host} not ready after {}s",
+                timeout.as_secs()
+            )));
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Restarts `ssh` with `command` whenever it has exited, until `stopped` disconnects.
+fn supervise(child: &Mutex<Child>, mut command: Command, host: &str, stopped: &mpsc::Receiver<()>) {
+    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(SUPERVISE_INTERVAL) {
+        let mut child = child.lock().expect("ssh tunnel lock poisoned");
+        if let Ok(Some(status)) = child.try_wait() {
+            warn!(host = %host, "ssh exited with {status}, restarting the tunnel");
+            match command.spawn() {
+                Ok(restarted) => *child = restarted,
+                Err(e) => warn!(host = %host, "Failed to restart ssh: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssh_command() {
+        let mut ssh = SshTunnelConfig {
+            host: "node.example.com".to_string(),
+            port: 2222,
+            user: None,
+            key_file: None,
+            remote_addr: None,
+            local_port: None,
+        };
+        let local_addr: SocketAddr = "127.0.0.1:18332".parse().unwrap();
+        let args = |ssh: &SshTunnelConfig| -> Vec<String> {
+            let command = ssh_command(ssh, local_addr, "127.0.0.1:8332");
+            assert_eq!(command.get_program(), "ssh");
+            command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+        };
+        let forward = [
+            "-N",
+            "-o",
+            "BatchMode=yes",
+            "-o",
+            "ExitOnForwardFailure=yes",
+            "-o",
+            "ServerAliveInterval=30",
+            "-L",
+            "127.0.0.1:18332:127.0.0.1:8332",
+            "-p",
+            "2222",
+        ];
+        assert_eq!(args(&ssh), [&forward[..], &["node.example.com"]].concat());
+
+        ssh.user = Some("bitcoin".to_string());
+        ssh.key_file = Some("/etc/id_ed25519".into());
+        let login = ["-i", "/etc/id_ed25519", "-o", "IdentitiesOnly=yes", "-l", "bitcoin", "node.example.com"];
+        assert_eq!(args(&ssh), [&forward[..], &login].concat());
+    }
+
+    #[test]
+    fn test_wait_ready() {
+        let local_addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        // ssh failing to log in or to forward exits
+        let mut child = Command::new("false").spawn().unwrap();
+        let error = wait_ready(&mut child, local_addr, "node.example.com", READY_TIMEOUT).unwrap_err().to_string();
+        assert!(error.contains("ssh to node.example.com exited with"), "{error}");
+
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let error = wait_ready(&mut child, local_addr, "node.example.com", Duration::ZERO).unwrap_err().to_string();
+        assert!(error.contains("not ready after 0s"), "{error}");
+
+        let listener = TcpListener::bind(local_addr).unwrap();
+        assert!(wait_ready(&mut child, local_addr, "node.example.com", READY_TIMEOUT).is_ok());
+        drop(listener);
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
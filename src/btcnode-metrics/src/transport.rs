@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use jsonrpc::client::Transport;
+use jsonrpc::{Request, Response};
+use prometheus::{IntCounter, Opts};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+/// Default `User-Agent` sent with every RPC request.
+pub const DEFAULT_USER_AGENT: &str = concat!("btcnode-metrics/", env!("CARGO_PKG_VERSION"));
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Upper bound on a response body, matching bitcoind's own RPC limits.
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024 * 1024;
+
+const CLOSED_BEFORE_RESPONSE: &str = "connection closed before response";
+
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("invalid RPC URL: {0}")]
+    Url(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("malformed HTTP response: {0}")]
+    Malformed(String),
+
+    #[error("HTTP status {status}: {body}")]
+    Status { status: u16, body: String },
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<TransportError> for jsonrpc::Error {
+    fn from(e: TransportError) -> Self {
+        jsonrpc::Error::Transport(Box::new(e))
+    }
+}
+
+/// Counters describing how the transport's connections are used.
+#[derive(Clone)]
+pub struct ConnectionCounters {
+    pub opened: IntCounter,
+    pub reused: IntCounter,
+}
+
+impl ConnectionCounters {
+    /// Creates the counters with a node's const labels (see [`crate::metrics::node_labels`]).
+    pub fn new(labels: HashMap<String, String>) -> Result<Self, prometheus::Error> {
+        Ok(Self {
+            opened: IntCounter::with_opts(
+                Opts::new(
+                    "bitcoin_collector_rpc_connections_opened_total",
+                    "Number of new HTTP connections opened to the node",
+                )
+                .const_labels(labels.clone()),
+            )?,
+            reused: IntCounter::with_opts(
+                Opts::new(
+                    "bitcoin_collector_rpc_connections_reused_total",
+                    "Number of RPC requests sent over an already open keep-alive connection",
+                )
+                .const_labels(labels),
+            )?,
+        })
+    }
+}
+
+/// JSON-RPC over HTTP/1.1 transport for bitcoind.
+///
+/// Used in place of the transport built into `corepc-client` so that request
+/// headers (e.g. `User-Agent`) can be controlled and connections are kept alive
+/// across calls and scrapes instead of being re-established for every request.
+pub struct HttpTransport {
+    url: String,
+    host: String,
+    path: String,
+    basic_auth: String,
+    user_agent: String,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    idle: Mutex<Vec<BufReader<TcpStream>>>,
+    counters: ConnectionCounters,
+}
+
+impl HttpTransport {
+    pub fn new(
+        url: &str,
+        user: &str,
+        password: &str,
+        user_agent: &str,
+        counters: ConnectionCounters,
+    ) -> Result<Self, TransportError> {
+        let (host, path) = parse_http_url(url)?;
+
+        Ok(Self {
+            url: url.to_string(),
+            host,
+            path,
+            basic_auth: format!("Basic {}", base64::encode(format!("{user}:{password}"))),
+            user_agent: user_agent.to_string(),
+            connect_timeout: DEFAULT_TIMEOUT,
+            read_timeout: DEFAULT_TIMEOUT,
+            idle: Mutex::new(Vec::new()),
+            counters,
+        })
+    }
+
+    /// Bounds connecting separately from reading and writing on an open connection.
+    pub fn with_timeouts(mut self, connect: Duration, read: Duration) -> Self {
+        self.connect_timeout = connect;
+        self.read_timeout = read;
+        self
+    }
+
+    fn connect(&self) -> Result<BufReader<TcpStream>, TransportError> {
+        let conn = connect(&self.host, self.connect_timeout, self.read_timeout)?;
+        self.counters.opened.inc();
+        Ok(conn)
+    }
+
+    fn request<R: DeserializeOwned>(&self, body: impl Serialize) -> Result<R, TransportError> {
+        let body = serde_json::to_vec(&body)?;
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nAuthorization: {}\r\nUser-Agent: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: keep-alive\r\n\r\n",
+            self.path,
+            self.host,
+            self.basic_auth,
+            self.user_agent,
+            body.len(),
+        )
+        .into_bytes();
+        request.extend_from_slice(&body);
+
+        let idle = self.idle.lock().expect("connection pool poisoned").pop();
+        let (mut conn, status, response, keep_alive) = match idle {
+            Some(mut conn) => match exchange(&mut conn, &request) {
+                Ok((status, response, keep_alive)) => {
+                    self.counters.reused.inc();
+                    (conn, status, response, keep_alive)
+                }
+                // The node may have closed the idle connection; retry once on a fresh one
+                Err(e) if closed_before_response(&e) => {
+                    let mut conn = self.connect()?;
+                    let (status, response, keep_alive) = exchange(&mut conn, &request)?;
+                    (conn, status, response, keep_alive)
+                }
+                Err(e) => return Err(e),
+            },
+            None => {
+                let mut conn = self.connect()?;
+                let (status, response, keep_alive) = exchange(&mut conn, &request)?;
+                (conn, status, response, keep_alive)
+            }
+        };
+
+        if keep_alive {
+            // Only pool connections with no unread bytes left on them
+            if conn.buffer().is_empty() {
+                self.idle.lock().expect("connection pool poisoned").push(conn);
+            }
+        } else {
+            let _ = conn.get_mut().shutdown(std::net::Shutdown::Both);
+        }
+
+        // bitcoind reports RPC errors as JSON with a non-200 status, so only fall back
+        // to the raw status when the body isn't a JSON-RPC response (e.g. a 401)
+        match serde_json::from_slice(&response) {
+            Ok(parsed) => Ok(parsed),
+            Err(_) if status != 200 => Err(TransportError::Status {
+                status,
+                body: String::from_utf8_lossy(&response).into_owned(),
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Splits an `http://host:port/path` URL into its `host:port` and path. The path is kept
+/// whole, so an RPC server behind a reverse proxy at e.g. `http://proxy/node1/` is posted
+/// to at `/node1/`.
+pub(crate) fn parse_http_url(url: &str) -> Result<(String, String), TransportError> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| TransportError::Url(format!("{url} (only http:// is supported)")))?;
+    let (host, path) = match rest.find('/') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(TransportError::Url(format!("{url} (missing host)")));
+    }
+    Ok((host.to_string(), path.to_string()))
+}
+
+/// Opens a connection to `host` (`host:port`, or `host` for port 80), trying each resolved
+/// address in turn; reads and writes on it then time out after `io_timeout`.
+pub(crate) fn connect(
+    host: &str,
+    connect_timeout: Duration,
+    io_timeout: Duration,
+) -> Result<BufReader<TcpStream>, TransportError> {
+    // A colon after any IPv6 brackets separates the port
+    let has_port = host.rsplit_once(':').is_some_and(|(_, port)| !port.contains(']'));
+    let addrs = if has_port { host.to_socket_addrs()? } else { (host.trim_matches(['[', ']']), 80).to_socket_addrs()? };
+    let mut last_err = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, connect_timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(io_timeout))?;
+                stream.set_write_timeout(Some(io_timeout))?;
+                stream.set_nodelay(true)?;
+                return Ok(BufReader::new(stream));
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err
+        .map(TransportError::Io)
+        .unwrap_or_else(|| TransportError::Url(format!("{host} did not resolve"))))
+}
+
+/// Whether `e` is a pooled connection the peer closed before any of the response arrived,
+/// so the request can be resent on a fresh one. Anything else, a read timeout in particular,
+/// may mean the request is still running and must not be sent again.
+pub(crate) fn closed_before_response(e: &TransportError) -> bool {
+    match e {
+        TransportError::Malformed(message) => message == CLOSED_BEFORE_RESPONSE,
+        TransportError::Io(e) => matches!(
+            e.kind(),
+            io::ErrorKind::ConnectionReset | io::ErrorKind::BrokenPipe | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+/// Writes `request` and reads one HTTP/1.1 response, returning the status, body and
+/// whether the connection may be reused.
+pub(crate) fn exchange(
+    conn: &mut BufReader<TcpStream>,
+    request: &[u8],
+) -> Result<(u16, Vec<u8>, bool), TransportError> {
+    conn.get_mut().write_all(request)?;
+    conn.get_mut().flush()?;
+
+    let mut line = String::new();
+    if conn.read_line(&mut line)? == 0 {
+        return Err(TransportError::Malformed(CLOSED_BEFORE_RESPONSE.to_string()));
+    }
+    let mut parts = line.split_whitespace();
+    let version = parts.next().unwrap_or_default().to_string();
+    let status = parts
+        .next()
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| TransportError::Malformed(format!("bad status line: {}", line.trim_end())))?;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    let mut keep_alive = version == "HTTP/1.1";
+    loop {
+        line.clear();
+        if conn.read_line(&mut line)? == 0 {
+            return Err(TransportError::Malformed("connection closed in headers".to_string()));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                content_length = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| TransportError::Malformed(format!("bad content-length: {value}")))?,
+                );
+            }
+            "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+            "connection" => keep_alive = !value.eq_ignore_ascii_case("close"),
+            _ => {}
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            conn.read_line(&mut line)?;
+            let size = u64::from_str_radix(line.trim().split(';').next().unwrap_or_default(), 16)
+                .map_err(|_| TransportError::Malformed(format!("bad chunk size: {}", line.trim())))?;
+            if size == 0 {
+                // Consume the (empty) trailer section
+                loop {
+                    line.clear();
+                    if conn.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                        break;
+                    }
+                }
+                break;
+            }
+            if body.len() as u64 + size > MAX_RESPONSE_BYTES {
+                return Err(TransportError::Malformed("response too large".to_string()));
+            }
+            conn.by_ref().take(size).read_to_end(&mut body)?;
+            line.clear();
+            conn.read_line(&mut line)?;
+        }
+    } else if let Some(length) = content_length {
+        if length > MAX_RESPONSE_BYTES {
+            return Err(TransportError::Malformed("response too large".to_string()));
+        }
+        conn.by_ref().take(length).read_to_end(&mut body)?;
+        if (body.len() as u64) < length {
+            return Err(TransportError::Malformed("truncated response body".to_string()));
+        }
+    } else {
+        // Without a length the body runs until the server closes the connection
+        conn.by_ref().take(MAX_RESPONSE_BYTES).read_to_end(&mut body)?;
+        keep_alive = false;
+    }
+
+    Ok((status, body, keep_alive))
+}
+
+impl Transport for HttpTransport {
+    fn send_request(&self, request: Request) -> Result<Response, jsonrpc::Error> {
+        Ok(self.request(request)?)
+    }
+
+    fn send_batch(&self, requests: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        Ok(self.request(requests)?)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
@@ -1,15 +1,43 @@
+use std::collections::BTreeMap;
+
 use axum::extract::State;
-use axum::http::{StatusCode, header};
+use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::IntoResponse;
+use axum::Json;
 
 use crate::state::AppState;
 
-pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+pub async fn metrics_handler(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
     let service = state.service.clone();
-    match tokio::task::spawn_blocking(move || service.scrape()).await {
-        Ok(body) => (
-            StatusCode::OK,
-            [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+    let openmetrics = state.exemplars
+        && headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/openmetrics-text"));
+    let scrape = move || {
+        if openmetrics {
+            // A per-scrape collection runs in this span, so the exemplars it records refer to it
+            tracing::info_span!("scrape").in_scope(|| service.scrape_openmetrics())
+        } else {
+            service.scrape_checked()
+        }
+    };
+    // In background mode a scrape only encodes the latest refresh's values, so it runs
+    // on the runtime; otherwise it collects from the node and needs a blocking thread
+    let scraped = if state.refresh.is_some() {
+        Ok(scrape())
+    } else {
+        tokio::task::spawn_blocking(scrape).await
+    };
+    let content_type = if openmetrics {
+        btcnode_metrics::openmetrics::CONTENT_TYPE
+    } else {
+        "text/plain; version=0.0.4; charset=utf-8"
+    };
+    match scraped {
+        Ok((body, had_error)) => (
+            if had_error && state.fail_on_error { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK },
+            [(header::CONTENT_TYPE, content_type)],
             body,
         )
             .into_response(),
@@ -21,6 +49,64 @@ pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse
     }
 }
 
-pub async fn health_handler() -> impl IntoResponse {
-    (StatusCode::OK, "ok")
+pub async fn debug_collect_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let service = state.service.clone();
+    match tokio::task::spawn_blocking(move || service.collect()).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("metrics collection failed: {e}"),
+        )
+            .into_response(),
+    }
+}
+
+/// Reports of the latest collections, oldest first; see [`btcnode_metrics::MetricsService::history`].
+pub async fn debug_history_handler(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.service.history())
+}
+
+/// Wakes the background refresh loop for an immediate collection, e.g. after
+/// `invalidateblock`; answers before the collection runs.
+pub async fn refresh_handler(State(state): State<AppState>) -> impl IntoResponse {
+    match &state.refresh {
+        Some(refresh) => {
+            refresh.notify_one();
+            (StatusCode::ACCEPTED, "refresh scheduled").into_response()
+        }
+        None => (
+            StatusCode::CONFLICT,
+            "background refresh is not enabled (collector.refresh_interval_seconds)",
+        )
+            .into_response(),
+    }
+}
+
+/// Plain "ok" by default; detailed node status when the client asks for JSON, keyed by
+/// node alias when several nodes are configured.
+pub async fn health_handler(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let wants_json = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+    if !wants_json {
+        return (StatusCode::OK, "ok").into_response();
+    }
+
+    let service = state.service.clone();
+    match tokio::task::spawn_blocking(move || service.status()).await {
+        Ok(mut statuses) if statuses.len() == 1 => Json(statuses.remove(0).1).into_response(),
+        Ok(statuses) => Json(
+            statuses
+                .into_iter()
+                .map(|(alias, status)| (alias.unwrap_or_default(), status))
+                .collect::<BTreeMap<_, _>>(),
+        )
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("status check failed: {e}"),
+        )
+            .into_response(),
+    }
 }
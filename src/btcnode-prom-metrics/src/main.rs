@@ -1,25 +1,50 @@
 mod handlers;
+mod middleware;
 mod state;
 
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use axum::Router;
-use axum::routing::get;
-use clap::Parser;
+use axum::middleware::{from_fn, from_fn_with_state};
+use axum::routing::{get, post};
+use clap::{Parser, Subcommand};
+use prometheus::{GaugeVec, Opts, Registry};
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
 use tokio::net::TcpListener;
-use tracing::info;
+use tokio::sync::{Notify, watch};
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
 use tracing_subscriber::EnvFilter;
 
-use btcnode_metrics::{AppConfig, BitcoinMetrics, BitcoinNode, MetricsCollector, MetricsService};
+use btcnode_metrics::config::{ConfigSource, NodeConfig, resolve_listen_addr};
+use btcnode_metrics::node::{SUPPORTED_CORE_VERSIONS, core_version_string};
+use btcnode_metrics::{
+    AppConfig, BitcoinMetrics, BitcoinNode, ErrorKind, FileWriter, MetricsCollector, MetricsService, NodeClient,
+    RemoteWriter, SshTunnel,
+};
 
 use crate::state::AppState;
 
 #[derive(Parser)]
 #[command(name = "btc-metrics", about = "Bitcoin node metrics exporter for Prometheus")]
 struct Cli {
-    #[arg(short, long, default_value = "config.toml")]
-    config: PathBuf,
+    /// Config file, or `-` to read it from stdin. Without one, the TOML in BTC_METRICS_CONFIG
+    /// is used when set, and config.toml otherwise
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Connect to each configured node, print its chain state and exit
+    Check,
 }
 
 #[tokio::main]
@@ -29,35 +54,639 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let cli = Cli::parse();
-    let config = AppConfig::load(&cli.config)?;
+    let source = ConfigSource::resolve(cli.config.as_deref());
+    let config = AppConfig::load_from(&source)?;
+    info!(source = %source, config = %config, "Loaded configuration");
+
+    if let Some(Command::Check) = cli.command {
+        return check(&config);
+    }
+
+    // Every node's metrics go on one registry, told apart by their `node` label
+    let registry = Registry::new();
+    let mut collectors = Vec::with_capacity(config.nodes.len());
+    let mut tunnels = Vec::new();
+    for node_config in &config.nodes {
+        let rpc_url = node_config.redacted_rpc_url();
+        info!(rpc_url = %rpc_url, node = node_config.alias.as_deref(), "Connecting to Bitcoin node");
+
+        let tunnel = SshTunnel::open(node_config)?;
+        let node = match &tunnel {
+            Some(tunnel) => BitcoinNode::new(&tunnel.node_config(node_config))?,
+            None => BitcoinNode::new(node_config)?,
+        };
+        tunnels.extend(tunnel);
+        if node_config.strict_chain
+            && let Some(expected) = &node_config.expected_chain
+        {
+            match node.get_blockchain_info() {
+                Ok(info) if info.chain != *expected => {
+                    let url = node_config.redacted_rpc_url();
+                    anyhow::bail!("{url} is on chain {:?}, expected {expected:?}", info.chain)
+                }
+                Ok(_) => {}
+                // Can't tell yet; bitcoin_chain_mismatch reports it once the node answers
+                Err(e) => warn!(node = node_config.alias.as_deref(), "Could not verify expected_chain: {e}"),
+            }
+        }
+        let version_supported = check_core_version(&node, node_config)?;
+        let metrics = BitcoinMetrics::builder()
+            .registry(&registry)
+            .node(node_config.alias.as_deref())
+            .instance(node_config.instance_label()?.as_deref())
+            .sections(config.collector.enabled_sections())
+            .extra_metrics(&config.extra_metrics)
+            .build()?;
+        if let Some(supported) = version_supported {
+            metrics.core_version_supported.set(if supported { 1.0 } else { 0.0 });
+        }
+        node.register_metrics(&registry)?;
+        collectors.push(
+            MetricsCollector::with_config(node, metrics, config.collector.clone())
+                .with_expected_chain(node_config.expected_chain.clone())
+                .with_max_connections(node_config.max_connections)
+                .with_wallets(node_config.all_wallets())
+                .with_watched_descriptors(node_config.watch_descriptors.clone()),
+        );
+    }
+    let remote_writer = match &config.remote_write {
+        Some(remote_write) => {
+            let writer = RemoteWriter::new(&remote_write.url)?;
+            writer.register_metrics(&registry)?;
+            Some((Arc::new(writer), Duration::from_secs(remote_write.interval_seconds)))
+        }
+        None => None,
+    };
+    let file_writer = match &config.file_output {
+        Some(file_output) => {
+            let writer = FileWriter::new(file_output)?;
+            writer.register_metrics(&registry)?;
+            Some((Arc::new(writer), Duration::from_secs(file_output.interval_seconds)))
+        }
+        None => None,
+    };
+    register_mode(&registry, &config)?;
+    let service = Arc::new(MetricsService::with_collectors(collectors));
+
+    let refresh = config
+        .collector
+        .refresh_interval_seconds
+        .map(|seconds| (Arc::new(Notify::new()), Duration::from_secs(seconds)));
+    let auth_token = Arc::new(RwLock::new(config.server.read_auth_token()?));
+    let state = AppState {
+        service: service.clone(),
+        refresh: refresh.as_ref().map(|(notify, _)| notify.clone()),
+        auth_token: auth_token.clone(),
+        fail_on_error: config.server.fail_on_error,
+        exemplars: config.server.exemplars,
+    };
+
+    let (app, admin_app) = routers(state, config.server.admin_addr.is_some());
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let mut servers = JoinSet::new();
+
+    // Bind every address before serving any so a bad address fails startup
+    let mut listeners = Vec::with_capacity(config.server.listen_addrs.len());
+    for name in &config.server.listen_addrs {
+        for addr in resolve_listen_addr(name)? {
+            let listener = bind_tcp(addr, &config.server)
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to bind {addr} (from {name}): {e}"))?;
+            listeners.push((addr, listener));
+        }
+    }
+    let mut admin_listeners = Vec::new();
+    if let Some(name) = &config.server.admin_addr {
+        for addr in resolve_listen_addr(name)? {
+            let listener = bind_tcp(addr, &config.server)
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to bind admin address {addr} (from {name}): {e}"))?;
+            admin_listeners.push((addr, listener));
+        }
+    }
+
+    for (addr, listener) in listeners {
+        info!(addr = %addr, "Listening for Prometheus scrapes");
+
+        let server = axum::serve(listener, app.clone()).with_graceful_shutdown(wait_for_shutdown(shutdown_rx.clone()));
+        servers.spawn(async move { server.await });
+    }
+    if let Some(admin_app) = &admin_app {
+        for (addr, listener) in admin_listeners {
+            info!(addr = %addr, "Listening for admin requests");
+
+            let server =
+                axum::serve(listener, admin_app.clone()).with_graceful_shutdown(wait_for_shutdown(shutdown_rx.clone()));
+            servers.spawn(async move { server.await });
+        }
+    }
+
+    #[cfg(unix)]
+    if let Some(path) = &config.server.unix_socket {
+        // A socket file left behind by an unclean exit would make bind fail
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = tokio::net::UnixListener::bind(path)?;
+        info!(path = %path.display(), "Listening for Prometheus scrapes");
+
+        let server = axum::serve(listener, app.clone()).with_graceful_shutdown(wait_for_shutdown(shutdown_rx.clone()));
+        servers.spawn(async move { server.await });
+    }
+
+    #[cfg(not(unix))]
+    if config.server.unix_socket.is_some() {
+        anyhow::bail!("unix_socket is only supported on Unix platforms");
+    }
 
-    info!(rpc_url = %config.node.rpc_url, "Connecting to Bitcoin node");
+    if let Some((notify, interval)) = refresh {
+        info!(interval_seconds = interval.as_secs(), "Collecting in the background");
 
-    let node = BitcoinNode::new(&config.node)?;
-    let metrics = BitcoinMetrics::new()?;
-    let collector = MetricsCollector::new(node, metrics);
-    let service = Arc::new(MetricsService::new(collector));
+        servers.spawn(refresh_loop(service.clone(), notify, interval, shutdown_rx.clone()));
+    }
 
-    let state = AppState { service };
+    if let Some((writer, interval)) = remote_writer {
+        info!(interval_seconds = interval.as_secs(), "Pushing metrics via remote write");
 
-    let app = Router::new()
+        servers.spawn(remote_write_loop(service.clone(), writer, interval, shutdown_rx.clone()));
+    }
+
+    if let Some((writer, interval)) = file_writer {
+        info!(interval_seconds = interval.as_secs(), "Writing metrics to a file");
+
+        servers.spawn(file_output_loop(service.clone(), writer, interval, shutdown_rx.clone()));
+    }
+
+    if let Some(interval) = service.descriptor_scan_interval() {
+        info!(interval_seconds = interval.as_secs(), "Scanning the UTXO set for watched descriptors");
+
+        servers.spawn(descriptor_scan_loop(service.clone(), interval, shutdown_rx.clone()));
+    }
+
+    #[cfg(unix)]
+    servers.spawn(reload_on_hangup(
+        source,
+        config.clone(),
+        service.clone(),
+        auth_token,
+        shutdown_rx.clone(),
+    ));
+
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(());
+    });
+
+    let shutdown_timeout = Duration::from_secs(config.server.shutdown_timeout_seconds);
+    let mut drained = true;
+    let serve = async {
+        while let Some(joined) = servers.join_next().await {
+            joined??;
+        }
+        anyhow::Ok(())
+    };
+    tokio::pin!(serve);
+    let result = tokio::select! {
+        result = &mut serve => result,
+        _ = wait_for_shutdown(shutdown_rx.clone()) => {
+            // The servers stop accepting right away; in-flight requests get a bounded drain
+            match tokio::time::timeout(shutdown_timeout, &mut serve).await {
+                Ok(result) => {
+                    info!("In-flight requests drained");
+                    result
+                }
+                Err(_) => {
+                    warn!(timeout_seconds = shutdown_timeout.as_secs(), "In-flight requests did not drain in time; exiting anyway");
+                    drained = false;
+                    Ok(())
+                }
+            }
+        }
+    };
+
+    #[cfg(unix)]
+    if let Some(path) = &config.server.unix_socket {
+        let _ = std::fs::remove_file(path);
+    }
+    // Explicitly, since exiting below would skip it and leave the ssh processes running
+    drop(tunnels);
+
+    // Returning would wait for the runtime's blocking scrapes, which may be the ones hung
+    if !drained {
+        std::process::exit(1);
+    }
+    result
+}
+
+/// Routers for `listen_addrs` and, with an `admin_addr`, for the admin address. Every route but
+/// `/health` requires the bearer token when `auth_token_file` is set.
+fn routers(state: AppState, separate_admin: bool) -> (Router, Option<Router>) {
+    let auth = from_fn_with_state(state.clone(), middleware::require_bearer_token);
+    let scrape_routes = Router::new()
         .route("/metrics", get(handlers::metrics_handler))
-        .route("/health", get(handlers::health_handler))
-        .with_state(state);
+        .route_layer(auth.clone())
+        .route("/health", get(handlers::health_handler));
+    let admin_routes = Router::new()
+        .route("/debug/collect", get(handlers::debug_collect_handler))
+        .route("/debug/history", get(handlers::debug_history_handler))
+        .route("/refresh", post(handlers::refresh_handler))
+        .route_layer(auth);
+    // With an admin_addr, the scrape addresses serve only /metrics and /health
+    let (app, admin_app) = if separate_admin {
+        (scrape_routes, Some(admin_routes.route("/health", get(handlers::health_handler))))
+    } else {
+        (scrape_routes.merge(admin_routes), None)
+    };
+    let app = app.layer(from_fn(middleware::log_requests)).with_state(state.clone());
+    let admin_app = admin_app.map(|admin| admin.layer(from_fn(middleware::log_requests)).with_state(state));
+    (app, admin_app)
+}
 
-    let listener = TcpListener::bind(&config.server.listen_addr).await?;
-    info!(addr = %config.server.listen_addr, "Listening for Prometheus scrapes");
+/// Exports `bitcoin_exporter_mode`: how metrics leave this exporter (`pull`, `remote_write`, `file_output`) and
+/// when they're collected (`background`, `per_scrape`), 1 for each mode in use.
+fn register_mode(registry: &Registry, config: &AppConfig) -> prometheus::Result<()> {
+    let mode = GaugeVec::new(
+        Opts::new(
+            "bitcoin_exporter_mode",
+            "Whether the exporter runs in this mode, from its config (1=active, 0=inactive)",
+        ),
+        &["mode"],
+    )?;
+    let pull = !config.server.listen_addrs.is_empty() || config.server.unix_socket.is_some();
+    let background = config.collector.refresh_interval_seconds.is_some();
+    for (name, active) in [
+        ("pull", pull),
+        ("remote_write", config.remote_write.is_some()),
+        ("file_output", config.file_output.is_some()),
+        ("background", background),
+        ("per_scrape", !background),
+    ] {
+        mode.with_label_values(&[name]).set(if active { 1.0 } else { 0.0 });
+    }
+    registry.register(Box::new(mode))
+}
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+/// Compares the node's Bitcoin Core version against [`SUPPORTED_CORE_VERSIONS`], warning when
+/// it's outside, or failing with `strict_version`. `None` when the node can't be asked yet.
+///
+/// Reads the raw `getnetworkinfo` result, since an unsupported version is exactly when the
+/// parsed one may fail.
+fn check_core_version(node: &BitcoinNode, node_config: &NodeConfig) -> anyhow::Result<Option<bool>> {
+    let alias = node_config.alias.as_deref();
+    let version = match node.call_json("getnetworkinfo", &[]) {
+        Ok(info) => info.get("version").and_then(|version| version.as_u64()),
+        Err(e) => {
+            warn!(node = alias, "Could not check the Bitcoin Core version: {e}");
+            return Ok(None);
+        }
+    };
+    let Some(version) = version else {
+        warn!(node = alias, "getnetworkinfo has no version; cannot check the Bitcoin Core version");
+        return Ok(None);
+    };
+    if SUPPORTED_CORE_VERSIONS.contains(&version) {
+        return Ok(Some(true));
+    }
+    let message = format!(
+        "{} runs Bitcoin Core {}, but this exporter supports {}.x through {}.x; some metrics may fail to parse. \
+         Upgrade the exporter, or use a supported Core version",
+        node_config.alias.clone().unwrap_or_else(|| node_config.redacted_rpc_url()),
+        core_version_string(version),
+        SUPPORTED_CORE_VERSIONS.start() / 10000,
+        SUPPORTED_CORE_VERSIONS.end() / 10000,
+    );
+    if node_config.strict_version {
+        anyhow::bail!(message);
+    }
+    warn!(node = alias, "{message}");
+    Ok(Some(false))
+}
 
+/// Calls `getblockchaininfo` on every node, reporting why any of them can't be used.
+fn check(config: &AppConfig) -> anyhow::Result<()> {
+    let mut failed = 0;
+    for node_config in &config.nodes {
+        let name = node_config.alias.clone().unwrap_or_else(|| node_config.redacted_rpc_url());
+        let result = SshTunnel::open(node_config).and_then(|tunnel| {
+            let tunneled = tunnel.as_ref().map(|tunnel| tunnel.node_config(node_config));
+            BitcoinNode::new(tunneled.as_ref().unwrap_or(node_config))?.get_blockchain_info()
+        });
+        match result {
+            Ok(info) => println!(
+                "{name}: ok (chain={}, blocks={}, verification_progress={:.6})",
+                info.chain, info.blocks, info.verification_progress
+            ),
+            Err(e) => {
+                failed += 1;
+                let hint = match e.kind() {
+                    ErrorKind::Auth => "authentication failed; check rpc_user and rpc_password",
+                    ErrorKind::Connection => "could not reach the RPC server; check the host and port in rpc_url",
+                    ErrorKind::VersionMismatch => "unexpected response; this Bitcoin Core version may be unsupported",
+                    ErrorKind::Rpc | ErrorKind::Config | ErrorKind::Other => "request failed",
+                };
+                eprintln!("{name}: {hint}: {e}");
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} node(s) failed the check", config.nodes.len());
+    }
     Ok(())
 }
 
+/// Binds a scrape or admin listener, with `tcp_backlog` and `tcp_keepalive_seconds` applied
+/// when set. Accepted connections inherit the keepalive options from the listening socket.
+async fn bind_tcp(addr: SocketAddr, server: &btcnode_metrics::config::ServerConfig) -> std::io::Result<TcpListener> {
+    if server.tcp_backlog.is_none() && server.tcp_keepalive_seconds.is_none() {
+        return TcpListener::bind(addr).await;
+    }
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    // As tokio's own bind does, so a restart can rebind while old connections linger
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    if let Some(seconds) = server.tcp_keepalive_seconds {
+        socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(Duration::from_secs(seconds)))?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    // tokio's default backlog, capped by the OS (net.core.somaxconn on Linux)
+    let backlog = server.tcp_backlog.unwrap_or(1024);
+    socket.listen(i32::try_from(backlog).unwrap_or(i32::MAX))?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Ctrl-C, or on Unix also SIGTERM, which systemd, Docker and Kubernetes stop services with.
 async fn shutdown_signal() {
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to install CTRL+C signal handler");
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install CTRL+C signal handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        signal(SignalKind::terminate())
+            .expect("failed to install SIGTERM signal handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
     info!("Shutdown signal received");
 }
+
+async fn wait_for_shutdown(mut rx: watch::Receiver<()>) {
+    let _ = rx.changed().await;
+}
+
+/// `interval` shifted by a random amount within `±jitter`.
+fn jittered(interval: Duration, jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return interval;
+    }
+    // RandomState is randomly seeded per instance, which is all the randomness this needs
+    let random = RandomState::new().build_hasher().finish();
+    let offset = jitter.mul_f64(random as f64 / u64::MAX as f64 * 2.0);
+    (interval + offset).saturating_sub(jitter)
+}
+
+/// Collects every `interval` (shifted by `refresh_jitter_seconds` each time), or at once
+/// when `notify` is signaled, until shutdown.
+async fn refresh_loop(
+    service: Arc<MetricsService<BitcoinNode>>,
+    notify: Arc<Notify>,
+    mut interval: Duration,
+    mut shutdown: watch::Receiver<()>,
+) -> std::io::Result<()> {
+    let mut next = tokio::time::Instant::now();
+    loop {
+        // Follow refresh_interval_seconds changes from a config reload
+        if let Some(reloaded) = service.refresh_interval()
+            && reloaded != interval
+        {
+            info!(interval_seconds = reloaded.as_secs(), "Background collection interval changed");
+            interval = reloaded;
+            next = tokio::time::Instant::now() + jittered(interval, service.refresh_jitter());
+        }
+        tokio::select! {
+            _ = tokio::time::sleep_until(next) => {}
+            _ = notify.notified() => debug!("Refresh requested"),
+            _ = shutdown.changed() => return Ok(()),
+        }
+        // Scheduled from the start of this collection; one that overruns the interval is
+        // followed by the next at once
+        next = tokio::time::Instant::now() + jittered(interval, service.refresh_jitter());
+
+        let service = service.clone();
+        // In a span of its own, which exemplars of the collection refer to
+        let refresh = move || tracing::info_span!("refresh").in_scope(|| service.refresh());
+        if let Err(e) = tokio::task::spawn_blocking(refresh).await {
+            warn!("Background collection task failed: {e}");
+        }
+    }
+}
+
+/// Re-reads the config on SIGHUP and applies its `[collector]` settings to the running
+/// collectors, and re-reads `auth_token_file`; changes to anything else are logged as needing
+/// a restart. A config read from stdin can't be read again, so only the token is reloaded.
+#[cfg(unix)]
+async fn reload_on_hangup(
+    source: ConfigSource,
+    mut current: AppConfig,
+    service: Arc<MetricsService<BitcoinNode>>,
+    auth_token: Arc<RwLock<Option<String>>>,
+    mut shutdown: watch::Receiver<()>,
+) -> std::io::Result<()> {
+    use btcnode_metrics::config::ServerConfig;
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {}
+            _ = shutdown.changed() => return Ok(()),
+        }
+
+        if source == ConfigSource::Stdin {
+            info!("Config was read from stdin; reloading only auth_token_file");
+            reload_auth_token(&current.server, &auth_token);
+            continue;
+        }
+        info!(source = %source, "Reloading configuration");
+        let config = match AppConfig::load_from(&source) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Config reload failed; keeping the current configuration: {e}");
+                continue;
+            }
+        };
+        let background = |config: &AppConfig| config.collector.refresh_interval_seconds.is_some();
+        let scan_interval = |config: &AppConfig| config.collector.descriptor_scan_interval_seconds;
+        // auth_token_file is the one [server] option applied in place
+        let server = ServerConfig { auth_token_file: current.server.auth_token_file.clone(), ..config.server.clone() };
+        for (changed, option) in [
+            (config.nodes != current.nodes, "[node]"),
+            (server != current.server, "[server]"),
+            (config.remote_write != current.remote_write, "[remote_write]"),
+            (config.file_output != current.file_output, "[file_output]"),
+            (config.extra_metrics != current.extra_metrics, "[[extra_metric]]"),
+            (background(&config) != background(&current), "enabling or disabling refresh_interval_seconds"),
+            (scan_interval(&config) != scan_interval(&current), "descriptor_scan_interval_seconds"),
+        ] {
+            if changed {
+                warn!("Config reload: changes to {option} need a restart to take effect");
+            }
+        }
+        match service.reload(&config.collector) {
+            Ok(()) => info!(config = %config, "Applied reloaded collector configuration"),
+            Err(e) => warn!("Failed to apply reloaded collector configuration: {e}"),
+        }
+        current.collector = config.collector;
+
+        // Also picks up a rotated token when the file itself is all that changed
+        if reload_auth_token(&config.server, &auth_token) {
+            current.server.auth_token_file = config.server.auth_token_file;
+        }
+    }
+}
+
+/// Replaces the scrape token with the one `server.auth_token_file` now holds, keeping the
+/// current one if that can't be read; returns whether it was replaced.
+#[cfg(unix)]
+fn reload_auth_token(server: &btcnode_metrics::config::ServerConfig, auth_token: &RwLock<Option<String>>) -> bool {
+    use std::sync::PoisonError;
+
+    match server.read_auth_token() {
+        Ok(token) => {
+            *auth_token.write().unwrap_or_else(PoisonError::into_inner) = token;
+            true
+        }
+        Err(e) => {
+            warn!("Failed to reload the auth token; keeping the current one: {e}");
+            false
+        }
+    }
+}
+
+/// Gathers and pushes metrics every `interval` until shutdown.
+async fn remote_write_loop(
+    service: Arc<MetricsService<BitcoinNode>>,
+    writer: Arc<RemoteWriter>,
+    interval: Duration,
+    mut shutdown: watch::Receiver<()>,
+) -> std::io::Result<()> {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => return Ok(()),
+        }
+
+        let service = service.clone();
+        let writer = writer.clone();
+        match tokio::task::spawn_blocking(move || writer.push(&service.gather())).await {
+            Ok(Ok(())) => debug!("Pushed metrics via remote write"),
+            Ok(Err(e)) => warn!("Remote write failed: {e}"),
+            Err(e) => warn!("Remote write task failed: {e}"),
+        }
+    }
+}
+
+/// Writes the scrape body to the metrics file every `interval` until shutdown.
+async fn file_output_loop(
+    service: Arc<MetricsService<BitcoinNode>>,
+    writer: Arc<FileWriter>,
+    interval: Duration,
+    mut shutdown: watch::Receiver<()>,
+) -> std::io::Result<()> {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => return Ok(()),
+        }
+
+        let service = service.clone();
+        let writer = writer.clone();
+        match tokio::task::spawn_blocking(move || writer.write(&service.scrape())).await {
+            Ok(Ok(())) => debug!("Wrote metrics file"),
+            Ok(Err(e)) => warn!("Writing the metrics file failed: {e}"),
+            Err(e) => warn!("Metrics file task failed: {e}"),
+        }
+    }
+}
+
+/// Looks up the watched descriptors' balances every `interval` until shutdown, starting
+/// right away. Shutdown doesn't wait for a scan in progress, which may take minutes.
+async fn descriptor_scan_loop(
+    service: Arc<MetricsService<BitcoinNode>>,
+    interval: Duration,
+    mut shutdown: watch::Receiver<()>,
+) -> std::io::Result<()> {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => return Ok(()),
+        }
+
+        let service = service.clone();
+        tokio::select! {
+            scanned = tokio::task::spawn_blocking(move || service.scan_descriptors()) => {
+                if let Err(e) = scanned {
+                    warn!("Descriptor scan task failed: {e}");
+                }
+            }
+            _ = shutdown.changed() => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_admin_routes_require_the_token() {
+        let node_config: NodeConfig =
+            toml::from_str("rpc_url = \"http://127.0.0.1:1\"\nrpc_user = \"u\"\nrpc_password = \"p\"").unwrap();
+        let collector = MetricsCollector::new(BitcoinNode::new(&node_config).unwrap(), BitcoinMetrics::new().unwrap());
+        let state = AppState {
+            service: Arc::new(MetricsService::new(collector)),
+            refresh: None,
+            auth_token: Arc::new(RwLock::new(Some("s3cret".to_string()))),
+            fail_on_error: false,
+            exemplars: false,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, routers(state, false).0).into_future());
+
+        let status = |request: &'static str, token: Option<&'static str>| async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let auth = token.map(|token| format!("Authorization: Bearer {token}\r\n")).unwrap_or_default();
+            let request =
+                format!("{request} HTTP/1.1\r\nHost: test\r\nContent-Length: 0\r\n{auth}Connection: close\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response.split_whitespace().nth(1).unwrap().parse::<u16>().unwrap()
+        };
+        assert_eq!(status("POST /refresh", None).await, 401);
+        assert_eq!(status("GET /debug/history", None).await, 401);
+        assert_eq!(status("GET /debug/collect", Some("wrong")).await, 401);
+        assert_eq!(status("GET /debug/history", Some("s3cret")).await, 200);
+        assert_eq!(status("GET /health", None).await, 200);
+    }
+}
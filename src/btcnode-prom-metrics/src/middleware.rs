@@ -0,0 +1,55 @@
+use std::sync::PoisonError;
+use std::time::Instant;
+
+use axum::extract::{Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tracing::debug;
+
+use crate::state::AppState;
+
+/// Logs method, path, status and handling time for every request.
+///
+/// Only the URI path is logged; headers (including any `Authorization`) and the
+/// query string are deliberately left out.
+pub async fn log_requests(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    debug!(
+        %method,
+        %path,
+        status = response.status().as_u16(),
+        duration_ms = start.elapsed().as_secs_f64() * 1000.0,
+        "Handled request"
+    );
+    response
+}
+
+/// Answers 401 unless the request carries `Authorization: Bearer <token>` with the token
+/// from `auth_token_file`; lets everything through when no token is configured.
+pub async fn require_bearer_token(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let expected = state.auth_token.read().unwrap_or_else(PoisonError::into_inner).clone();
+    if let Some(expected) = expected {
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.as_bytes().strip_prefix(b"Bearer "));
+        if !presented.is_some_and(|token| constant_time_eq(token, expected.as_bytes())) {
+            debug!(path = %request.uri().path(), "Rejected request without a valid bearer token");
+            return (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], "unauthorized").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Compares every byte rather than stopping at the first difference, so response timing
+/// doesn't reveal how much of a guessed token was right. Only the length can leak.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+    a.len() == b.len() && std::hint::black_box(diff) == 0
+}
@@ -1,15 +1,28 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use btcnode_metrics::{BitcoinNode, MetricsService};
+use tokio::sync::Notify;
 
 pub struct AppState {
     pub service: Arc<MetricsService<BitcoinNode>>,
+    /// Wakes the background refresh loop; `None` when collecting per scrape.
+    pub refresh: Option<Arc<Notify>>,
+    /// Bearer token `/metrics` requires, from `auth_token_file`; swapped on SIGHUP.
+    pub auth_token: Arc<RwLock<Option<String>>>,
+    /// Answer scrapes with an RPC error or timeout with 503; see `server.fail_on_error`.
+    pub fail_on_error: bool,
+    /// Serve OpenMetrics with exemplars to scrapers that accept it; see `server.exemplars`.
+    pub exemplars: bool,
 }
 
 impl Clone for AppState {
     fn clone(&self) -> Self {
         Self {
             service: Arc::clone(&self.service),
+            refresh: self.refresh.clone(),
+            auth_token: Arc::clone(&self.auth_token),
+            fail_on_error: self.fail_on_error,
+            exemplars: self.exemplars,
         }
     }
 }